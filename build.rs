@@ -0,0 +1,31 @@
+//! Embed the vendored js libraries, see `vendor/README.md`.
+
+use std::fs;
+use std::path::Path;
+
+const MERMAID_JS: &str = "vendor/mermaid/mermaid.min.js";
+
+fn main() {
+    println!("cargo:rerun-if-changed=vendor");
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let dest = Path::new(&out_dir).join("mermaid.min.js");
+
+    let js = match fs::read_to_string(MERMAID_JS) {
+        Ok(js) if !js.trim().is_empty() => js,
+        _ => {
+            let message = format!(
+                "{} is missing: run ./vendor/fetch.sh and commit it; mermaid diagrams fail to render without it",
+                MERMAID_JS
+            );
+            // A release is what gets installed, and must render offline; a debug build may
+            // go without, such as to work on other parts without network access.
+            if std::env::var("PROFILE").as_deref() == Ok("release") {
+                panic!("{}", message);
+            }
+            println!("cargo:warning={}", message);
+            String::new()
+        }
+    };
+    fs::write(dest, js).unwrap();
+}
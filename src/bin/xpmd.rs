@@ -1,11 +1,42 @@
 use std::fs;
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
+use clap::builder::PossibleValuesParser;
 use clap::Parser;
 use clap::Subcommand;
+use xp_md2html::book::Book;
+use xp_md2html::check::markdown_files;
+use xp_md2html::check::repo_root;
+use xp_md2html::check::Checker;
+use xp_md2html::config::Config;
+use xp_md2html::config::Settings;
+use xp_md2html::config::CONFIG_FILE;
+use xp_md2html::doctor;
+use xp_md2html::doctor::Os;
+use xp_md2html::export::publish::GitPublisher;
+use xp_md2html::export::publish::GITHUB_RAW_URL;
+use xp_md2html::export::Feature;
+use xp_md2html::export::Target;
+use xp_md2html::md::transform::FootnoteStyle;
+use xp_md2html::preview::Preview;
+use xp_md2html::render::batch;
+use xp_md2html::render::batch::RenderOptions;
+use xp_md2html::render::batch::DEFAULT_NAME_TEMPLATE;
+use xp_md2html::render::check_format;
+use xp_md2html::render::output_format;
+use xp_md2html::render::report::RenderReport;
+use xp_md2html::render::watch;
 use xp_md2html::render::with_chrome::WithChrome;
+use xp_md2html::render::OUTPUT_FORMATS;
+use xp_md2html::server::RenderServer;
 
 #[derive(Parser)]
 #[command(name = "xpmd")]
@@ -14,39 +45,187 @@ use xp_md2html::render::with_chrome::WithChrome;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Config file [default: xpmd.toml in the current directory or a parent]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Use the settings of [profile.<PROFILE>] in the config
+    #[arg(long, global = true)]
+    profile: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Render HTML content to image using headless Chrome
     Render {
-        /// Input file path (HTML content)
+        /// Input files or globs such as "docs/**/*.html", or "-" for stdin
+        #[arg(short, long, required = true, num_args = 1..)]
+        input: Vec<String>,
+
+        /// Output file path, or "-" for stdout
+        #[arg(short, long, conflicts_with = "out_dir")]
+        output: Option<PathBuf>,
+
+        /// Output directory, to render several inputs
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Output file name in --out-dir; {stem}, {format} and {dir} of the input are replaced
+        #[arg(long, default_value = DEFAULT_NAME_TEMPLATE, requires = "out_dir")]
+        name: String,
+
+        /// Number of files to render at a time [default: number of CPUs]
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Output format [default: from the --output extension, else png]
+        #[arg(short, long, ignore_case = true, value_parser = PossibleValuesParser::new(OUTPUT_FORMATS))]
+        format: Option<String>,
+
+        /// Window width for rendering [default: 1000]
+        #[arg(short, long)]
+        width: Option<u32>,
+
+        /// Window height for rendering [default: 2000]
+        #[arg(long)]
+        height: Option<u32>,
+
+        /// Device pixels per CSS pixel, e.g. 2 for a sharper image twice as large [default: 1]
+        #[arg(long)]
+        scale: Option<f64>,
+
+        /// MIME type of input content, or a shortcut such as svg or md (detected from the extension or content if not specified)
+        #[arg(short, long)]
+        mime: Option<String>,
+
+        /// Base path for assets (for HTML files with relative paths)
+        #[arg(short, long)]
+        base: Option<PathBuf>,
+
+        /// Stylesheet to add to HTML and markdown pages; may be repeated
+        #[arg(long)]
+        css: Vec<PathBuf>,
+
+        /// Render again when an input, a stylesheet it links to, or a file in --base changes
+        #[arg(long)]
+        watch: bool,
+
+        /// Print one JSON object per render, or per error, instead of status messages
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Export markdown for a publishing platform, rendering what it can not display to images
+    Export {
+        /// Input markdown file path
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Output file path
+        /// Output file path: markdown for zhihu, html for wechat
         #[arg(short, long)]
         output: PathBuf,
 
-        /// Output format: png, jpg, jpeg, pdf
+        /// Export target: zhihu, wechat
+        #[arg(short, long)]
+        target: Target,
+
+        /// Also render these to images: math, table, mermaid, dot, code, alert. E.g. "table,code"
+        #[arg(long, value_delimiter = ',')]
+        images: Vec<Feature>,
+
+        /// Directory to write images to [default: "assets" next to the output]
+        #[arg(short, long)]
+        assets: Option<PathBuf>,
+
+        /// Image format: png, jpg
         #[arg(short, long, default_value = "png")]
         format: String,
 
-        /// Window width for rendering
-        #[arg(short, long, default_value = "1000")]
-        width: u32,
+        /// How footnotes are written: keep, end-notes, inline, link-references [default: by target]
+        #[arg(long)]
+        footnotes: Option<FootnoteStyle>,
 
-        /// Window height for rendering  
-        #[arg(long, default_value = "2000")]
-        height: u32,
+        /// Push images to this git repository and link to them by url, e.g. git@github.com:user/assets.git
+        #[arg(long)]
+        publish: Option<String>,
 
-        /// MIME type of input content (auto-detected if not specified)
-        #[arg(short, long)]
-        mime: Option<String>,
+        /// Branch to push images to
+        #[arg(long, default_value = "main", requires = "publish")]
+        publish_branch: String,
 
-        /// Base path for assets (for HTML files with relative paths)
+        /// Url of a pushed image; {repo}, {branch} and {path} are replaced
+        #[arg(long, default_value = GITHUB_RAW_URL, requires = "publish")]
+        publish_url: String,
+    },
+
+    /// Build a book from the chapters listed in a SUMMARY.md
+    Book {
+        #[command(subcommand)]
+        command: BookCommands,
+    },
+
+    /// Check relative links, anchors and images of markdown files
+    Check {
+        /// Markdown files, or directories to search for them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Directory that links starting with `/` are resolved against. Default: the root of
+        /// the git repository of the current directory; without one such links are not checked
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+
+    /// Preview markdown in a directory in the browser, reloading pages when files change
+    Serve {
+        /// Directory to serve
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:3000")]
+        listen: SocketAddr,
+    },
+
+    /// Run a http service: POST markup to /render and get an image back
+    Server {
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+
+        /// Largest request body accepted, in bytes
+        #[arg(long, default_value = "10485760")]
+        max_body: usize,
+
+        /// Renders to run at a time [default: number of CPUs]
+        #[arg(long)]
+        concurrency: Option<usize>,
+
+        /// Seconds a render may take before Chrome is killed
+        #[arg(long, default_value = "60")]
+        timeout: u64,
+    },
+
+    /// Check that Chrome, ImageMagick and fonts are installed, and how to fix what is missing
+    Doctor,
+}
+
+#[derive(Subcommand)]
+enum BookCommands {
+    /// Convert every chapter to a html page with a sidebar and links to the previous and next
+    Build {
+        /// Directory of SUMMARY.md, or of a "src" dir with it
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Output directory [default: "book" in DIR]
         #[arg(short, long)]
-        base: Option<PathBuf>,
+        output: Option<PathBuf>,
+
+        /// Also write all chapters into one file: html, or pdf printed by Chrome
+        #[arg(long)]
+        single: Option<PathBuf>,
     },
 }
 
@@ -54,107 +233,600 @@ enum Commands {
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    let settings = load_settings(cli.config.as_deref(), cli.profile.as_deref())?;
+    if let Some(chrome) = &settings.chrome {
+        WithChrome::set_chrome_path(chrome);
+    }
+
     match cli.command {
         Commands::Render {
             input,
             output,
+            out_dir,
+            name,
+            jobs,
             format,
             width,
             height,
+            scale,
             mime,
             base,
+            css,
+            watch,
+            json,
+        } => {
+            let mut error_report = RenderReport::new(
+                input.join(" "),
+                output
+                    .as_ref()
+                    .or(out_dir.as_ref())
+                    .map_or(String::new(), |p| p.display().to_string()),
+                format.as_deref().unwrap_or_default(),
+            );
+
+            let result: Result<()> = async {
+                // Without --format, the extension of --output tells the format.
+                let format = match &output {
+                    Some(output) => output_format(format.as_deref(), output)?,
+                    None => format,
+                };
+
+                // Flags override the config.
+                let flags = Settings {
+                    format,
+                    width,
+                    height,
+                    scale,
+                    base,
+                    css: Some(css).filter(|c| !c.is_empty()),
+                    chrome: None,
+                };
+                let options = RenderOptions {
+                    mime,
+                    ..settings.merge(flags).render_options()
+                };
+                error_report.format = options.format.to_lowercase();
+                let jobs = jobs.unwrap_or_else(|| {
+                    std::thread::available_parallelism()
+                        .map(|n| n.get())
+                        .unwrap_or(1)
+                });
+
+                match out_dir {
+                    Some(out_dir) => {
+                        let pairs = batch_pairs(input, &out_dir, &name, &options)?;
+                        if watch {
+                            watch_command(pairs, options, jobs, json).await?;
+                        } else {
+                            render_batch_command(pairs, out_dir, jobs, options, json).await?;
+                        }
+                    }
+                    None => {
+                        let [input] = <[String; 1]>::try_from(input).map_err(|_| {
+                            anyhow::anyhow!("Several inputs need --out-dir instead of --output")
+                        })?;
+                        let input = PathBuf::from(input);
+                        let output = output.context("--output or --out-dir is required")?;
+
+                        if watch {
+                            if is_stdio(&input) || is_stdio(&output) {
+                                anyhow::bail!("--watch needs files, not stdin or stdout");
+                            }
+                            check_format(&options.format)?;
+                            watch_command(vec![(input, output)], options, jobs, json).await?;
+                        } else {
+                            render_command(input, output, options, json).await?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            .await;
+
+            // In JSON mode, errors before any render are reported as a render that failed.
+            if let (true, Err(e)) = (json, &result) {
+                error_report.set_error(e);
+                println!("{}", error_report.to_json());
+                std::process::exit(1);
+            }
+            result?;
+        }
+        Commands::Export {
+            input,
+            output,
+            target,
+            images,
+            assets,
+            format,
+            footnotes,
+            publish,
+            publish_branch,
+            publish_url,
+        } => {
+            let publisher =
+                publish.map(|repo| GitPublisher::new(repo, publish_branch, publish_url));
+            export_command(
+                input, output, target, images, assets, format, footnotes, publisher,
+            )
+            .await?;
+        }
+        Commands::Book {
+            command:
+                BookCommands::Build {
+                    dir,
+                    output,
+                    single,
+                },
+        } => {
+            book_build_command(dir, output, single).await?;
+        }
+        Commands::Check { paths, root } => {
+            check_command(paths, root)?;
+        }
+        Commands::Serve { dir, listen } => {
+            println!("Serving {} at http://{}/", dir.display(), listen);
+            let mut preview = Preview::new(dir);
+            preview.render = settings.render_options();
+            preview.serve(listen).await?;
+        }
+        Commands::Server {
+            listen,
+            max_body,
+            concurrency,
+            timeout,
         } => {
-            render_command(input, output, format, width, height, mime, base).await?;
+            let mut server = RenderServer::new();
+            server.defaults = settings.render_options();
+            server.max_body = max_body;
+            server.timeout = Duration::from_secs(timeout);
+            if let Some(concurrency) = concurrency {
+                server.concurrency = concurrency;
+            }
+            println!(
+                "Listening on http://{}/ ({} renders at a time)",
+                listen, server.concurrency
+            );
+            server.serve(listen).await?;
+        }
+        Commands::Doctor => {
+            doctor_command().await?;
         }
     }
 
     Ok(())
 }
 
+/// The settings of the config file, `path` or the nearest `xpmd.toml`, with `profile` applied.
+fn load_settings(path: Option<&Path>, profile: Option<&str>) -> Result<Settings> {
+    let path = match path {
+        Some(path) => Some(path.to_path_buf()),
+        None => Config::find(&std::env::current_dir()?),
+    };
+
+    match path {
+        Some(path) => Config::load(&path)?.settings(profile),
+        None if profile.is_some() => {
+            anyhow::bail!(
+                "--profile needs a config file, but no {} is found",
+                CONFIG_FILE
+            )
+        }
+        None => Ok(Settings::default()),
+    }
+}
+
+/// Render one input, printing status messages, or with `json` a [`RenderReport`] of the
+/// render, or of why it failed, which exits with 1.
 async fn render_command(
     input: PathBuf,
     output: PathBuf,
-    format: String,
-    width: u32,
-    height: u32,
-    mime: Option<String>,
-    base: Option<PathBuf>,
+    options: RenderOptions,
+    json: bool,
 ) -> Result<()> {
-    // Validate input file exists
-    if !input.exists() {
-        anyhow::bail!("Input file does not exist: {}", input.display());
+    let start = Instant::now();
+    let mut report = RenderReport::new(input.display(), output.display(), &options.format);
+    let result = render_one(&input, &output, &options, json, &mut report).await;
+    if !json {
+        return result;
     }
 
-    // Read input content as string
-    let content = fs::read_to_string(&input)
-        .with_context(|| format!("Failed to read input file: {}", input.display()))?;
+    report.durations.total = start.elapsed().as_secs_f64();
+    if let Err(e) = &result {
+        report.set_error(e);
+    }
 
-    // Determine MIME type
-    let mime_type = mime.unwrap_or_else(|| {
-        // Try to determine from file extension
-        input
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| match ext.to_lowercase().as_str() {
-                "html" | "htm" => "text/html",
-                "svg" => "image/svg+xml",
-                "xml" => "application/xml",
-                _ => "text/html", // Default fallback
-            })
-            .unwrap_or("text/html")
-            .to_string()
-    });
-
-    // Validate output format
-    match format.to_lowercase().as_str() {
-        "png" | "jpg" | "jpeg" | "pdf" => {}
-        _ => anyhow::bail!(
-            "Unsupported output format: {}. Supported: png, jpg, jpeg, pdf",
-            format
-        ),
+    // The report goes to stderr when stdout carries the image.
+    if is_stdio(&output) {
+        eprintln!("{}", report.to_json());
+    } else {
+        println!("{}", report.to_json());
+    }
+    if result.is_err() {
+        std::process::exit(1);
     }
+    Ok(())
+}
 
-    println!(
+async fn render_one(
+    input: &Path,
+    output: &Path,
+    options: &RenderOptions,
+    json: bool,
+    report: &mut RenderReport,
+) -> Result<()> {
+    let from_stdin = is_stdio(input);
+    let to_stdout = is_stdio(output);
+
+    // Status messages go to stderr when stdout carries the image, and JSON replaces them.
+    let status = |msg: String| {
+        if json {
+            return;
+        }
+        if to_stdout {
+            eprintln!("{}", msg);
+        } else {
+            println!("{}", msg);
+        }
+    };
+
+    // Read input content as string
+    let start = Instant::now();
+    let content = if from_stdin {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read input from stdin")?;
+        content
+    } else {
+        // Validate input file exists
+        if !input.exists() {
+            anyhow::bail!("Input file does not exist: {}", input.display());
+        }
+
+        fs::read_to_string(input)
+            .with_context(|| format!("Failed to read input file: {}", input.display()))?
+    };
+    report.durations.read = start.elapsed().as_secs_f64();
+
+    check_format(&options.format)?;
+
+    status(format!(
         "Rendering {} to {} ({}x{}, format: {})",
         input.display(),
         output.display(),
-        width,
-        height,
-        format
-    );
+        options.width,
+        options.height,
+        options.format
+    ));
 
     // Create output directory if it doesn't exist
-    if let Some(parent) = output.parent() {
+    if let Some(parent) = output.parent().filter(|_| !to_stdout) {
         fs::create_dir_all(parent)
             .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
     }
 
     // Render using Chrome
-    let image_data = WithChrome::render_markup(
-        &mime_type,
-        &content,
-        &format.to_lowercase(),
-        Some(width),
-        Some(height),
-        base.as_deref(),
-    )
+    let rendered = batch::render_content_report(input, &content, options, report).await;
+    let image_data = if json {
+        rendered?
+    } else {
+        rendered.with_context(install_hint)?
+    };
+
+    // Write output
+    let start = Instant::now();
+    if to_stdout {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(&image_data)
+            .and_then(|_| stdout.flush())
+            .context("Failed to write output to stdout")?;
+    } else {
+        fs::write(output, &image_data)
+            .with_context(|| format!("Failed to write output file: {}", output.display()))?;
+    }
+    report.durations.write = start.elapsed().as_secs_f64();
+
+    status(format!("✅ Successfully rendered to: {}", output.display()));
+    status(format!("📊 Output size: {} bytes", image_data.len()));
+    for warning in &report.warnings {
+        status(format!("⚠️ {}", warning));
+    }
+
+    Ok(())
+}
+
+/// Render files, then render them again on every change, printing how long each took, or
+/// with `json` a [`RenderReport`] of every render.
+async fn watch_command(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    options: RenderOptions,
+    jobs: usize,
+    json: bool,
+) -> Result<()> {
+    if !json {
+        println!("Watching {} files; press Ctrl-C to stop", pairs.len());
+    }
+
+    watch::watch(pairs, &options, jobs, |r| {
+        if json {
+            println!("{}", r.report.to_json());
+            return;
+        }
+        match &r.result {
+            Ok(size) => println!(
+                "✅ {} -> {} in {:.2}s ({} bytes)",
+                r.input.display(),
+                r.output.display(),
+                r.elapsed.as_secs_f64(),
+                size
+            ),
+            Err(e) => eprintln!(
+                "❌ {} failed in {:.2}s: {:#}",
+                r.input.display(),
+                r.elapsed.as_secs_f64(),
+                e
+            ),
+        }
+    })
     .await
-    .with_context(|| {
+}
+
+/// Expand the inputs of a batch and pair each with its path in `out_dir`.
+fn batch_pairs(
+    patterns: Vec<String>,
+    out_dir: &Path,
+    name: &str,
+    options: &RenderOptions,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    check_format(&options.format)?;
+    if patterns.iter().any(|p| is_stdio(Path::new(p))) {
+        anyhow::bail!("stdin can not be rendered to --out-dir; use --output instead");
+    }
+
+    let inputs = batch::expand_inputs(&patterns)?;
+    batch::plan(&inputs, out_dir, name, &options.format.to_lowercase())
+}
+
+/// Render several files into `out_dir`, print a table of the results, or with `json` a
+/// [`RenderReport`] of each, and exit with 1 if any failed.
+async fn render_batch_command(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    out_dir: PathBuf,
+    jobs: usize,
+    options: RenderOptions,
+    json: bool,
+) -> Result<()> {
+    if json {
+        let rendered = batch::render_batch(pairs, &options, jobs).await?;
+        for r in &rendered {
+            println!("{}", r.report.to_json());
+        }
+        if rendered.iter().any(|r| r.result.is_err()) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "Rendering {} files to {} ({} at a time)",
+        pairs.len(),
+        out_dir.display(),
+        jobs
+    );
+
+    let start = Instant::now();
+    let rendered = batch::render_batch(pairs, &options, jobs).await?;
+
+    println!();
+    println!("{:<6}  {:>7}  {:>10}  FILE", "STATUS", "TIME", "BYTES");
+    for r in &rendered {
+        let time = format!("{:.2}s", r.elapsed.as_secs_f64());
+        match &r.result {
+            Ok(size) => println!(
+                "{:<6}  {:>7}  {:>10}  {} -> {}",
+                "ok",
+                time,
+                size,
+                r.input.display(),
+                r.output.display()
+            ),
+            Err(e) => println!(
+                "{:<6}  {:>7}  {:>10}  {}: {:#}",
+                "FAILED",
+                time,
+                "-",
+                r.input.display(),
+                e
+            ),
+        }
+    }
+    println!();
+
+    let failed = rendered.iter().filter(|r| r.result.is_err()).count();
+    let elapsed = start.elapsed().as_secs_f64();
+    if failed > 0 {
+        eprintln!(
+            "❌ {} of {} files failed in {:.2}s\n{}",
+            failed,
+            rendered.len(),
+            elapsed,
+            install_hint()
+        );
+        std::process::exit(1);
+    }
+
+    println!("✅ Rendered {} files in {:.2}s", rendered.len(), elapsed);
+    Ok(())
+}
+
+/// What to do when rendering fails, for this OS.
+fn install_hint() -> String {
+    let os = Os::current();
+    format!(
         "Failed to render content. Make sure Chrome/Chromium and ImageMagick are installed and accessible.\n\
-         Chrome: On macOS: Install from https://www.google.com/chrome/\n\
-         Chrome: On Linux: sudo apt install chromium-browser (Ubuntu/Debian) or equivalent\n\
-         Chrome: On Windows: Install from https://www.google.com/chrome/\n\
-         ImageMagick: On macOS: brew install imagemagick\n\
-         ImageMagick: On Linux: sudo apt install imagemagick\n\
-         ImageMagick: On Windows: Install from https://imagemagick.org/"
-    })?;
+         Chrome: {}\n\
+         ImageMagick: {}\n\
+         Run `xpmd doctor` to check what is missing.",
+        os.chrome_hint(),
+        os.image_magick_hint()
+    )
+}
 
-    // Write output
-    fs::write(&output, &image_data)
+/// Run the checks of `xpmd doctor`, print them, and exit with 1 if any failed.
+async fn doctor_command() -> Result<()> {
+    let checks = doctor::run().await;
+    for check in &checks {
+        println!("{}", check);
+    }
+
+    let failed = checks.iter().filter(|c| !c.passed()).count();
+    if failed > 0 {
+        eprintln!("❌ {} of {} checks failed", failed, checks.len());
+        std::process::exit(1);
+    }
+    println!("✅ All {} checks passed", checks.len());
+    Ok(())
+}
+
+/// Whether a path argument is "-", which means stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn export_command(
+    input: PathBuf,
+    output: PathBuf,
+    target: Target,
+    images: Vec<Feature>,
+    assets: Option<PathBuf>,
+    format: String,
+    footnotes: Option<FootnoteStyle>,
+    publisher: Option<GitPublisher>,
+) -> Result<()> {
+    let md = fs::read_to_string(&input)
+        .with_context(|| format!("Failed to read input file: {}", input.display()))?;
+
+    let output_dir = output.parent().unwrap_or(Path::new("")).to_path_buf();
+    let asset_dir = assets.unwrap_or_else(|| output_dir.join("assets"));
+
+    // Link to images relative to the output if they are in the same tree.
+    let asset_url = match &publisher {
+        Some(publisher) => publisher.asset_url(),
+        None => asset_dir
+            .strip_prefix(&output_dir)
+            .unwrap_or(&asset_dir)
+            .display()
+            .to_string(),
+    };
+
+    let mut export = target.export(&asset_dir, asset_url);
+    export.image_format = format.to_lowercase();
+    export.features.extend(images);
+    if let Some(footnotes) = footnotes {
+        export.footnotes = footnotes;
+    }
+    export.source_dir = Some(input.parent().unwrap_or(Path::new("")).to_path_buf());
+
+    println!(
+        "Exporting {} to {} for {} (images in {})",
+        input.display(),
+        output.display(),
+        target,
+        asset_dir.display()
+    );
+
+    let exported = export
+        .run(&md)
+        .await
+        .with_context(|| format!("Failed to export {} for {}", input.display(), target))?;
+
+    if !output_dir.as_os_str().is_empty() {
+        fs::create_dir_all(&output_dir).with_context(|| {
+            format!(
+                "Failed to create output directory: {}",
+                output_dir.display()
+            )
+        })?;
+    }
+
+    // Push before writing the output, so that it never links to images that are not there.
+    if let Some(publisher) = &publisher {
+        if asset_dir.exists() {
+            let added = publisher.publish(&asset_dir)?;
+            println!(
+                "Published {} new images to {} {}",
+                added.len(),
+                publisher.repo,
+                publisher.branch
+            );
+        }
+    }
+
+    fs::write(&output, exported)
         .with_context(|| format!("Failed to write output file: {}", output.display()))?;
 
-    println!("✅ Successfully rendered to: {}", output.display());
-    println!("📊 Output size: {} bytes", image_data.len());
+    println!("✅ Successfully exported to: {}", output.display());
+
+    Ok(())
+}
+
+async fn book_build_command(
+    dir: PathBuf,
+    output: Option<PathBuf>,
+    single: Option<PathBuf>,
+) -> Result<()> {
+    let book = Book::load(&dir)?;
+    let output = output.unwrap_or_else(|| dir.join("book"));
+
+    let n = book.build(&output).await?;
+    println!(
+        "✅ Built {} chapters of {} to: {}",
+        n,
+        book.title(),
+        output.display()
+    );
+
+    if let Some(single) = single {
+        let is_pdf = single
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+
+        let data = if is_pdf {
+            book.build_pdf().await?
+        } else {
+            book.build_single().await?.into_bytes()
+        };
+        fs::write(&single, data)
+            .with_context(|| format!("Failed to write output file: {}", single.display()))?;
+        println!(
+            "✅ Successfully wrote the whole book to: {}",
+            single.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn check_command(paths: Vec<PathBuf>, root: Option<PathBuf>) -> Result<()> {
+    let files = markdown_files(&paths)?;
+
+    let mut checker = Checker::new();
+    checker.root = root.or_else(|| repo_root(Path::new(".")));
+    let mut n = 0;
+    for file in &files {
+        for issue in checker.check_file(file)? {
+            println!("{}", issue);
+            n += 1;
+        }
+    }
+
+    if n > 0 {
+        eprintln!("❌ {} broken links in {} files", n, files.len());
+        std::process::exit(1);
+    }
 
+    eprintln!("✅ Checked {} files, no broken links", files.len());
     Ok(())
 }
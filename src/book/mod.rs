@@ -0,0 +1,511 @@
+//! Build a book from markdown files listed in a `SUMMARY.md`, as mdBook does: a html site with
+//! a sidebar and links to the previous and next chapters, or a single html page or pdf.
+
+pub mod summary;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+pub use summary::Chapter;
+pub use summary::Summary;
+
+use crate::export::assets::is_local;
+use crate::md::escape_html;
+use crate::md::transform::EmbedImages;
+use crate::md::transform::HeadingIds;
+use crate::md::transform::RewriteLinks;
+use crate::md::transform::Transform;
+use crate::md::MarkdownToHtml;
+use crate::md::MathOutput;
+use crate::render::with_chrome::WithChrome;
+use crate::render::GITHUB_MARKDOWN_CSS;
+
+/// Layout of the sidebar and the chapter navigation around the markdown content.
+const BOOK_CSS: &str = r#"
+body { margin: 0; display: flex; }
+.sidebar { position: sticky; top: 0; height: 100vh; overflow-y: auto; flex: none; box-sizing: border-box; width: 280px; padding: 24px 16px; border-right: 1px solid #d1d9e0; font: 14px/1.8 -apple-system, BlinkMacSystemFont, "Segoe UI", "Noto Sans", Helvetica, Arial, sans-serif; }
+.sidebar .book-title { font-size: 16px; font-weight: 600; margin-bottom: 12px; }
+.sidebar ol { list-style: none; margin: 0; padding: 0; }
+.sidebar a { color: #1f2328; text-decoration: none; }
+.sidebar .active > a { color: #0969da; font-weight: 600; }
+.sidebar .draft { color: #59636e; }
+.sidebar .part-title { margin-top: 12px; font-weight: 600; }
+main { flex: 1; min-width: 0; }
+main .markdown-body { box-sizing: border-box; max-width: 980px; margin: 0 auto; padding: 45px; }
+.nav-chapters { display: flex; justify-content: space-between; box-sizing: border-box; max-width: 980px; margin: 0 auto; padding: 0 45px 45px; }
+.chapter + .chapter { margin-top: 48px; }
+@media print {
+  .sidebar, .nav-chapters { display: none; }
+  .chapter + .chapter { break-before: page; margin-top: 0; }
+}
+"#;
+
+/// A book: the chapters listed in a `SUMMARY.md`, converted with [`MarkdownToHtml`].
+#[derive(Debug, Clone)]
+pub struct Book {
+    /// The directory of `SUMMARY.md`, which chapter paths are relative to.
+    pub src_dir: PathBuf,
+
+    pub summary: Summary,
+
+    pub markdown: MarkdownToHtml,
+}
+
+impl Book {
+    /// Load the `SUMMARY.md` in `dir`, or in `dir/src` as mdBook lays out a book.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let src_dir = if dir.join("SUMMARY.md").exists() {
+            dir.to_path_buf()
+        } else {
+            dir.join("src")
+        };
+
+        let path = src_dir.join("SUMMARY.md");
+        let md = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read book summary: {}", path.display()))?;
+
+        Ok(Self {
+            src_dir,
+            summary: Summary::parse(&md)?,
+            markdown: MarkdownToHtml::new().with_math(MathOutput::MathMl),
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        self.summary.title.as_deref().unwrap_or("Book")
+    }
+
+    /// Write a html page for every chapter into `out_dir`, at the path of the chapter with a
+    /// `.html` extension, and an `index.html` that opens the first one. Links to chapters are
+    /// changed to `.html`, headings get ids to link to, and the other files in the source dir,
+    /// such as images, are copied. Return the number of pages written.
+    pub async fn build(&self, out_dir: &Path) -> anyhow::Result<usize> {
+        let pages: Vec<(&Chapter, &Path)> = self.summary.pages().collect();
+        let chapters: HashSet<PathBuf> = pages.iter().map(|(_, p)| p.to_path_buf()).collect();
+
+        for (i, &(chapter, path)) in pages.iter().enumerate() {
+            let page_path = path.with_extension("html");
+            let root = "../".repeat(page_path.components().count() - 1);
+
+            let chapter_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let chapters = chapters.clone();
+            let rewrite = RewriteLinks::new(move |url| site_url(url, &chapter_dir, &chapters));
+
+            let transforms: Vec<Box<dyn Transform>> =
+                vec![Box::new(rewrite), Box::new(HeadingIds::new())];
+            let content = self.render_chapter(path, transforms).await?;
+
+            let url = |p: &Path| format!("{}{}", root, url_path(&p.with_extension("html")));
+            let mut nav = String::new();
+            if let Some((prev, prev_path)) = i.checked_sub(1).map(|i| pages[i]) {
+                nav.push_str(&format!(
+                    "<a class=\"prev\" href=\"{}\">← {}</a>",
+                    url(prev_path),
+                    escape_html(&prev.title)
+                ));
+            }
+            if let Some((next, next_path)) = pages.get(i + 1) {
+                nav.push_str(&format!(
+                    "<a class=\"next\" href=\"{}\">{} →</a>",
+                    url(next_path),
+                    escape_html(&next.title)
+                ));
+            }
+
+            let sidebar = self.sidebar(Some(path), &url);
+            let title = format!("{} - {}", chapter.title, self.title());
+            let html = page(&title, &sidebar, &content, &nav);
+
+            let dest = out_dir.join(&page_path);
+            write(&dest, html.as_bytes())?;
+        }
+
+        if let Some((_, first)) = pages.first() {
+            let index = format!(
+                "<!DOCTYPE html>\n<meta charset=\"utf-8\">\n<meta http-equiv=\"refresh\" content=\"0; url={}\">\n",
+                url_path(&first.with_extension("html"))
+            );
+            write(&out_dir.join("index.html"), index.as_bytes())?;
+        }
+
+        copy_assets(&self.src_dir, out_dir, out_dir)?;
+
+        Ok(pages.len())
+    }
+
+    /// Build one self-contained html page with every chapter, such as to print. Links between
+    /// chapters become links within the page, and local images are embedded. Heading ids are
+    /// prefixed with the id of their chapter, such as `setup-linux-install`.
+    pub async fn build_single(&self) -> anyhow::Result<String> {
+        let ids: HashMap<PathBuf, String> = self
+            .summary
+            .pages()
+            .map(|(_, p)| (p.to_path_buf(), chapter_id(p)))
+            .collect();
+
+        let mut content = String::new();
+        for (_, path) in self.summary.pages() {
+            let chapter_path = path.to_path_buf();
+            let chapter_ids = ids.clone();
+            let rewrite =
+                RewriteLinks::new(move |url| single_page_url(url, &chapter_path, &chapter_ids));
+            let embed = EmbedImages::new(&self.src_dir);
+            let heading_ids = HeadingIds::new().with_prefix(format!("{}-", ids[path]));
+
+            let transforms: Vec<Box<dyn Transform>> =
+                vec![Box::new(rewrite), Box::new(embed), Box::new(heading_ids)];
+            let html = self.render_chapter(path, transforms).await?;
+            content.push_str(&format!(
+                "<section class=\"chapter\" id=\"{}\">\n{}</section>\n",
+                ids[path], html
+            ));
+        }
+
+        let sidebar = self.sidebar(None, &|p: &Path| format!("#{}", ids[p]));
+        Ok(page(self.title(), &sidebar, &content, ""))
+    }
+
+    /// Print the single page of [`Self::build_single`] to pdf with headless chrome.
+    pub async fn build_pdf(&self) -> anyhow::Result<Vec<u8>> {
+        let html = self.build_single().await?;
+        WithChrome::print_pdf("text/html", &html, Some(&self.src_dir)).await
+    }
+
+    /// Convert a chapter to html, running `transforms` after the ones of [`Self::markdown`].
+    async fn render_chapter(
+        &self,
+        path: &Path,
+        transforms: Vec<Box<dyn Transform>>,
+    ) -> anyhow::Result<String> {
+        let file = self.src_dir.join(path);
+        let md = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read chapter: {}", file.display()))?;
+
+        let mut pipeline = self.markdown.clone().with_dangerous_html(true).pipeline();
+        for transform in transforms {
+            pipeline.push_boxed(transform);
+        }
+
+        pipeline
+            .run(&md)
+            .await
+            .with_context(|| format!("Failed to convert chapter: {}", file.display()))
+    }
+
+    /// The table of contents, with `current` highlighted. `url` builds the link to a chapter.
+    fn sidebar(&self, current: Option<&Path>, url: &dyn Fn(&Path) -> String) -> String {
+        let mut out = format!(
+            "<div class=\"book-title\">{}</div>\n<ol>\n",
+            escape_html(self.title())
+        );
+
+        for chapter in &self.summary.chapters {
+            let title = escape_html(&chapter.title);
+            let indent = chapter.depth.saturating_sub(1);
+
+            let item = match chapter.path.as_deref() {
+                None if chapter.depth == 0 => format!("<li class=\"part-title\">{}</li>", title),
+                None => format!(
+                    "<li class=\"draft\" style=\"padding-left: {}em\">{}</li>",
+                    indent, title
+                ),
+                Some(path) => format!(
+                    "<li{} style=\"padding-left: {}em\"><a href=\"{}\">{}</a></li>",
+                    if current == Some(path) {
+                        " class=\"active\""
+                    } else {
+                        ""
+                    },
+                    indent,
+                    url(path),
+                    title
+                ),
+            };
+            out.push_str(&item);
+            out.push('\n');
+        }
+
+        out.push_str("</ol>\n");
+        out
+    }
+}
+
+fn page(title: &str, sidebar: &str, content: &str, nav: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>{}</style>
+<style>{}</style>
+</head>
+<body>
+<nav class="sidebar">
+{}</nav>
+<main>
+<article class="markdown-body">
+{}</article>
+<nav class="nav-chapters">{}</nav>
+</main>
+</body>
+</html>
+"#,
+        escape_html(title),
+        GITHUB_MARKDOWN_CSS,
+        BOOK_CSS,
+        sidebar,
+        content,
+        nav
+    )
+}
+
+/// The url of a link in a page of the site: links to chapters are changed to `.html`.
+///
+/// `chapter_dir` is the dir of the chapter the link is in, and `chapters` are the paths of
+/// every chapter, relative to the source dir.
+fn site_url(url: &str, chapter_dir: &Path, chapters: &HashSet<PathBuf>) -> Option<String> {
+    if !is_local(url) {
+        return None;
+    }
+
+    let i = url.find(['#', '?']).unwrap_or(url.len());
+    let (path, rest) = url.split_at(i);
+    let decoded = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+    if !chapters.contains(&normalize(&chapter_dir.join(decoded.as_ref()))) {
+        return None;
+    }
+
+    let stem = path.strip_suffix(".md")?;
+    Some(format!("{}.html{}", stem, rest))
+}
+
+/// The url of a link in the single page: a link to a chapter becomes a link to its section or
+/// to the anchor in it, and other local urls are made relative to the source dir.
+///
+/// `chapter` is the path of the chapter the link is in, and `ids` the id of every chapter,
+/// which prefixes the ids of its headings.
+fn single_page_url(url: &str, chapter: &Path, ids: &HashMap<PathBuf, String>) -> Option<String> {
+    if let Some(anchor) = url.strip_prefix('#') {
+        let id = ids.get(chapter)?;
+        return Some(anchor_url(id, anchor));
+    }
+
+    if !is_local(url) {
+        return None;
+    }
+
+    let i = url.find(['#', '?']).unwrap_or(url.len());
+    let (path, rest) = url.split_at(i);
+    let path = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+    let chapter_dir = chapter.parent().unwrap_or(Path::new(""));
+    let target = normalize(&chapter_dir.join(path.as_ref()));
+
+    if let Some(id) = ids.get(&target) {
+        let anchor = rest.strip_prefix('#').unwrap_or_default();
+        return Some(anchor_url(id, anchor));
+    }
+
+    Some(format!("{}{}", url_path(&target), rest))
+}
+
+/// The url of `#anchor` in the chapter `id` of the single page, or of the chapter if the
+/// anchor is empty.
+fn anchor_url(id: &str, anchor: &str) -> String {
+    if anchor.is_empty() {
+        return format!("#{}", id);
+    }
+    let anchor = percent_encoding::percent_decode_str(anchor).decode_utf8_lossy();
+    format!("#{}-{}", id, anchor)
+}
+
+/// The id of the section of a chapter in the single page, such as "setup-linux" for
+/// `setup/linux.md`.
+fn chapter_id(path: &Path) -> String {
+    path.with_extension("")
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Resolve `.` and `..` in a relative path without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A relative path as a url path, with `/` separators.
+fn url_path(path: &Path) -> String {
+    let parts: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+fn write(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create dir: {}", dir.display()))?;
+    }
+    fs::write(path, data).with_context(|| format!("Failed to write file: {}", path.display()))
+}
+
+/// Copy the files in `src` that are not markdown into `dest`, skipping hidden ones and
+/// `out_dir`, which may be in the source dir.
+fn copy_assets(src: &Path, dest: &Path, out_dir: &Path) -> anyhow::Result<()> {
+    let out_dir = fs::canonicalize(out_dir).unwrap_or_else(|_| out_dir.to_path_buf());
+
+    for entry in
+        fs::read_dir(src).with_context(|| format!("Failed to read dir: {}", src.display()))?
+    {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default();
+
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            if fs::canonicalize(&path).is_ok_and(|p| p == out_dir) {
+                continue;
+            }
+            copy_assets(&path, &dest.join(name), &out_dir)?;
+        } else if path.extension().is_none_or(|e| e != "md") {
+            fs::create_dir_all(dest)?;
+            fs::copy(&path, dest.join(name))
+                .with_context(|| format!("Failed to copy file: {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site_url() {
+        let chapters = HashSet::from([
+            PathBuf::from("sub/a/b.md"),
+            PathBuf::from("b.md"),
+            PathBuf::from("sub/c d.md"),
+        ]);
+        let url = |u: &str| site_url(u, Path::new("sub"), &chapters);
+
+        assert_eq!(url("a/b.md"), Some("a/b.html".to_string()));
+        assert_eq!(url("../b.md#x?"), Some("../b.html#x?".to_string()));
+        assert_eq!(url("c%20d.md"), Some("c%20d.html".to_string()));
+        assert_eq!(url("notes.md"), None);
+        assert_eq!(url("b.png"), None);
+        assert_eq!(url("https://a.com/b.md"), None);
+        assert_eq!(url("#b.md"), None);
+    }
+
+    #[test]
+    fn test_single_page_url() {
+        let ids = HashMap::from([
+            (PathBuf::from("a.md"), "a".to_string()),
+            (PathBuf::from("sub/b c.md"), "sub-b-c".to_string()),
+        ]);
+        let url = |u: &str| single_page_url(u, Path::new("sub/b c.md"), &ids);
+
+        assert_eq!(url("../a.md"), Some("#a".to_string()));
+        assert_eq!(url("../a.md#%E4%B8%AD"), Some("#a-中".to_string()));
+        assert_eq!(url("b%20c.md#usage"), Some("#sub-b-c-usage".to_string()));
+        assert_eq!(url("./b c.md#"), Some("#sub-b-c".to_string()));
+        assert_eq!(url("#usage"), Some("#sub-b-c-usage".to_string()));
+        assert_eq!(url("../img/a.png"), Some("img/a.png".to_string()));
+        assert_eq!(url("x.pdf?dl=1"), Some("sub/x.pdf?dl=1".to_string()));
+        assert_eq!(url("https://a.com/a.md"), None);
+    }
+
+    #[test]
+    fn test_chapter_id() {
+        assert_eq!(chapter_id(Path::new("setup/On Linux.md")), "setup-on-linux");
+    }
+
+    #[tokio::test]
+    async fn test_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("guide/img")).unwrap();
+        fs::write(
+            src.join("SUMMARY.md"),
+            "# Guide\n\n- [Intro](intro.md)\n  - [Usage](guide/usage.md)\n- [Later]()\n",
+        )
+        .unwrap();
+        fs::write(
+            src.join("intro.md"),
+            "# Intro\n\nSee [usage](guide/usage.md#opts), [notes](notes.md).\n",
+        )
+        .unwrap();
+        fs::write(
+            src.join("guide/usage.md"),
+            "# Usage\n\n![a](img/a.png) [back](../intro.md)\n\n## Opts\n",
+        )
+        .unwrap();
+        fs::write(src.join("guide/img/a.png"), "A").unwrap();
+
+        let book = Book::load(dir.path()).unwrap();
+        let out = dir.path().join("book");
+        assert_eq!(book.build(&out).await.unwrap(), 2);
+
+        let intro = fs::read_to_string(out.join("intro.html")).unwrap();
+        assert!(intro.contains("<title>Intro - Guide</title>"));
+        assert!(intro.contains(
+            "See <a href=\"guide/usage.html#opts\">usage</a>, <a href=\"notes.md\">notes</a>"
+        ));
+        assert!(
+            intro.contains("<h1><a id=\"intro\" class=\"anchor\" href=\"#intro\"></a>Intro</h1>")
+        );
+        assert!(intro.contains(
+            "<li class=\"active\" style=\"padding-left: 0em\"><a href=\"intro.html\">Intro</a></li>"
+        ));
+        assert!(intro.contains("<li class=\"draft\" style=\"padding-left: 0em\">Later</li>"));
+        assert!(intro.contains(
+            "<nav class=\"nav-chapters\"><a class=\"next\" href=\"guide/usage.html\">Usage →</a></nav>"
+        ));
+
+        let usage = fs::read_to_string(out.join("guide/usage.html")).unwrap();
+        assert!(usage.contains("<a href=\"../intro.html\">back</a>"));
+        assert!(usage.contains("<h2><a id=\"opts\" class=\"anchor\" href=\"#opts\"></a>Opts</h2>"));
+        assert!(usage.contains("<a class=\"prev\" href=\"../intro.html\">← Intro</a>"));
+        assert!(usage
+            .contains("<li style=\"padding-left: 0em\"><a href=\"../intro.html\">Intro</a></li>"));
+
+        assert!(fs::read_to_string(out.join("index.html"))
+            .unwrap()
+            .contains("url=intro.html"));
+        assert_eq!(fs::read(out.join("guide/img/a.png")).unwrap(), b"A");
+        assert!(!out.join("SUMMARY.md").exists());
+
+        let single = book.build_single().await.unwrap();
+        assert!(single.contains("<section class=\"chapter\" id=\"guide-usage\">\n<h1><a id=\"guide-usage-usage\" class=\"anchor\" href=\"#guide-usage-usage\"></a>Usage</h1>"));
+        assert!(single.contains("<a id=\"guide-usage-opts\""));
+        assert!(single.contains("See <a href=\"#guide-usage-opts\">usage</a>"));
+        assert!(single.contains("<img src=\"data:image/png;base64,QQ==\" alt=\"a\" />"));
+        assert!(single.contains("<a href=\"#intro\">Intro</a>"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Parse the table of contents of a book from a `SUMMARY.md`, in the format of mdBook.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use markdown::mdast::Node;
+
+use crate::md;
+
+/// An entry of the table of contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+
+    /// The markdown file, relative to the source dir. `None` for a draft chapter, which is
+    /// listed but has no page, or for a part title.
+    pub path: Option<PathBuf>,
+
+    /// Nesting level in the table of contents: 0 for a part title, 1 for a top level chapter.
+    pub depth: usize,
+}
+
+/// The table of contents of a book.
+///
+/// ```markdown
+/// # My book
+///
+/// [Introduction](README.md)
+///
+/// - [Setup](setup.md)
+///   - [Linux](setup/linux.md)
+/// - [Draft]()
+///
+/// ## Reference
+///
+/// - [Options](options.md)
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// The first `#` heading.
+    pub title: Option<String>,
+
+    /// Chapters and part titles in order.
+    pub chapters: Vec<Chapter>,
+}
+
+impl Summary {
+    pub fn parse(md: &str) -> anyhow::Result<Self> {
+        let root = md::parse(md)?;
+        let mut summary = Summary::default();
+
+        for node in root.children().map(|c| c.as_slice()).unwrap_or_default() {
+            match node {
+                Node::Heading(h) if h.depth == 1 && summary.title.is_none() => {
+                    summary.title = Some(node.to_string());
+                }
+                Node::Heading(_) => summary.chapters.push(Chapter {
+                    title: node.to_string(),
+                    path: None,
+                    depth: 0,
+                }),
+                // Prefix and suffix chapters, such as an introduction.
+                Node::Paragraph(_) => {
+                    md::walk(node, &mut |n| {
+                        if let Node::Link(_) = n {
+                            summary.chapters.push(chapter(n, 1));
+                        }
+                    });
+                }
+                Node::List(_) => summary.list(node, 1),
+                _ => {}
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// The chapters that have a page and their paths, in reading order.
+    pub fn pages(&self) -> impl Iterator<Item = (&Chapter, &Path)> {
+        self.chapters
+            .iter()
+            .filter_map(|c| Some((c, c.path.as_deref()?)))
+    }
+
+    fn list(&mut self, list: &Node, depth: usize) {
+        for item in list.children().map(|c| c.as_slice()).unwrap_or_default() {
+            for child in item.children().map(|c| c.as_slice()).unwrap_or_default() {
+                match child {
+                    Node::Paragraph(p) => {
+                        let link = p.children.iter().find(|n| matches!(n, Node::Link(_)));
+                        let node = link.unwrap_or(child);
+                        self.chapters.push(chapter(node, depth));
+                    }
+                    Node::List(_) => self.list(child, depth + 1),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// A chapter from a link, or a draft from plain text.
+fn chapter(node: &Node, depth: usize) -> Chapter {
+    let path = match node {
+        Node::Link(link) if !link.url.is_empty() => {
+            let url = percent_encoding::percent_decode_str(&link.url).decode_utf8_lossy();
+            Some(PathBuf::from(url.trim_start_matches("./")))
+        }
+        _ => None,
+    };
+
+    Chapter {
+        title: node.to_string(),
+        path,
+        depth,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary() {
+        let md = "# My *book*\n\n[Intro](README.md)\n\n- [Setup](./setup.md)\n  - [On Linux](setup/on%20linux.md)\n- [Draft]()\n- Plain\n\n## Reference\n\n1. [Options](options.md)\n\n---\n\n[Thanks](thanks.md)\n";
+        let summary = Summary::parse(md).unwrap();
+
+        let ch = |title: &str, path: Option<&str>, depth| Chapter {
+            title: title.to_string(),
+            path: path.map(PathBuf::from),
+            depth,
+        };
+
+        assert_eq!(summary.title.as_deref(), Some("My book"));
+        assert_eq!(summary.chapters, vec![
+            ch("Intro", Some("README.md"), 1),
+            ch("Setup", Some("setup.md"), 1),
+            ch("On Linux", Some("setup/on linux.md"), 2),
+            ch("Draft", None, 1),
+            ch("Plain", None, 1),
+            ch("Reference", None, 0),
+            ch("Options", Some("options.md"), 1),
+            ch("Thanks", Some("thanks.md"), 1),
+        ]);
+        assert_eq!(summary.pages().count(), 5);
+    }
+}
@@ -0,0 +1,466 @@
+//! Check the relative links, anchors and images of markdown files.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use markdown::mdast::Node;
+
+use crate::export::assets::is_local;
+use crate::export::assets::resolve_local;
+use crate::md;
+use crate::Mime;
+
+/// A broken link found in a markdown file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub url: String,
+    pub kind: IssueKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The file a link or an image refers to does not exist.
+    MissingFile,
+
+    /// No heading or html `id` in the target file matches the `#anchor` of a link.
+    MissingAnchor,
+
+    /// An image source is not an image according to [`Mime::get`].
+    NotAnImage,
+
+    /// The markdown file a link refers to for an anchor, or the checked file itself, can not
+    /// be read or is not utf-8.
+    Unreadable,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            IssueKind::MissingFile => "missing-file",
+            IssueKind::MissingAnchor => "missing-anchor",
+            IssueKind::NotAnImage => "not-an-image",
+            IssueKind::Unreadable => "unreadable",
+        };
+        f.write_str(s)
+    }
+}
+
+/// One issue per line, such as `docs/a.md:3:5: missing-file: b.md`.
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.kind,
+            self.url
+        )
+    }
+}
+
+/// Checks markdown files, caching the anchors of every file that links point to.
+#[derive(Debug, Default)]
+pub struct Checker {
+    /// The anchors of a file, `None` if it is unreadable.
+    anchors: HashMap<PathBuf, Option<HashSet<String>>>,
+
+    /// The directory that urls starting with `/`, such as `/docs/a.md`, are resolved against,
+    /// such as the root of the repository. Without one such urls are not checked.
+    pub root: Option<PathBuf>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Check the markdown file at `path`. A file that can not be read is reported as an
+    /// [`IssueKind::Unreadable`] issue at its start, so that other files are still checked.
+    pub fn check_file(&mut self, path: &Path) -> anyhow::Result<Vec<Issue>> {
+        match fs::read_to_string(path) {
+            Ok(md) => self.check_markdown(&md, path),
+            Err(_) => Ok(vec![unreadable(path)]),
+        }
+    }
+
+    /// Check the markdown `md` read from `path`; relative urls are resolved against the
+    /// directory of `path`.
+    pub fn check_markdown(&mut self, md: &str, path: &Path) -> anyhow::Result<Vec<Issue>> {
+        let Ok(root) = md::parse(md) else {
+            return Ok(vec![unreadable(path)]);
+        };
+        self.anchors
+            .entry(cache_key(path))
+            .or_insert_with(|| Some(anchors(&root)));
+
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        // Definitions used by an image reference must be images too.
+        let mut image_ids = HashSet::new();
+        md::walk(&root, &mut |node| {
+            if let Node::ImageReference(r) = node {
+                image_ids.insert(r.identifier.clone());
+            }
+        });
+
+        let mut links = vec![];
+        md::walk(&root, &mut |node| {
+            let (url, is_image) = match node {
+                Node::Link(l) => (&l.url, false),
+                Node::Image(i) => (&i.url, true),
+                Node::Definition(d) => (&d.url, image_ids.contains(&d.identifier)),
+                _ => return,
+            };
+            let (line, column) = node
+                .position()
+                .map(|p| (p.start.line, p.start.column))
+                .unwrap_or_default();
+            links.push((url.clone(), is_image, line, column));
+        });
+
+        let mut issues = vec![];
+        for (url, is_image, line, column) in links {
+            if let Some(kind) = self.check_url(base_dir, path, &url, is_image) {
+                issues.push(Issue {
+                    file: path.to_path_buf(),
+                    line,
+                    column,
+                    url,
+                    kind,
+                });
+            }
+        }
+
+        Ok(issues)
+    }
+
+    fn check_url(
+        &mut self,
+        base_dir: &Path,
+        path: &Path,
+        url: &str,
+        is_image: bool,
+    ) -> Option<IssueKind> {
+        if let Some(anchor) = url.strip_prefix('#') {
+            if is_image {
+                return None;
+            }
+            return self.check_anchor(path, anchor);
+        }
+
+        if !is_local(url) {
+            return None;
+        }
+
+        let target = match url.strip_prefix('/') {
+            Some(rel) => resolve_local(self.root.as_ref()?, rel),
+            None => resolve_local(base_dir, url),
+        };
+        if !target.exists() {
+            return Some(IssueKind::MissingFile);
+        }
+
+        if is_image {
+            let ext = target
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let is_image = Mime::get(&ext).is_some_and(|m| m.starts_with("image/"));
+            return (!is_image).then_some(IssueKind::NotAnImage);
+        }
+
+        match url.split_once('#') {
+            Some((_, anchor)) if !anchor.is_empty() && is_markdown(&target) => {
+                self.check_anchor(&target, anchor)
+            }
+            _ => None,
+        }
+    }
+
+    fn check_anchor(&mut self, path: &Path, anchor: &str) -> Option<IssueKind> {
+        let anchor = percent_encoding::percent_decode_str(anchor).decode_utf8_lossy();
+
+        let anchors = self.anchors.entry(cache_key(path)).or_insert_with(|| {
+            let md = fs::read_to_string(path).ok()?;
+            Some(anchors(&md::parse(&md).ok()?))
+        });
+
+        match anchors {
+            Some(anchors) if anchors.contains(anchor.as_ref()) => None,
+            Some(_) => Some(IssueKind::MissingAnchor),
+            None => Some(IssueKind::Unreadable),
+        }
+    }
+}
+
+/// An issue for a markdown file that can not be checked at all.
+fn unreadable(path: &Path) -> Issue {
+    Issue {
+        file: path.to_path_buf(),
+        line: 1,
+        column: 1,
+        url: path.display().to_string(),
+        kind: IssueKind::Unreadable,
+    }
+}
+
+/// The anchors a document defines: the slug of every heading, and the `id` or `name` of html
+/// elements.
+pub fn anchors(root: &Node) -> HashSet<String> {
+    let mut anchors: HashSet<String> = heading_slugs(root).into_iter().collect();
+
+    md::walk(root, &mut |node| {
+        if let Node::Html(h) = node {
+            for attr in ["id=\"", "name=\""] {
+                for (i, _) in h.value.match_indices(attr) {
+                    let rest = &h.value[i + attr.len()..];
+                    if let Some(end) = rest.find('"') {
+                        anchors.insert(rest[..end].to_string());
+                    }
+                }
+            }
+        }
+    });
+
+    anchors
+}
+
+/// The anchor of every heading in document order, as GitHub generates them: the [`slug`], with
+/// a suffix `-1`, `-2`, ... if it is repeated.
+pub fn heading_slugs(root: &Node) -> Vec<String> {
+    let mut slugs = vec![];
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    md::walk(root, &mut |node| {
+        if let Node::Heading(_) = node {
+            let s = slug(&node.to_string());
+            let n = counts.entry(s.clone()).or_default();
+            if *n == 0 {
+                slugs.push(s);
+            } else {
+                slugs.push(format!("{s}-{n}"));
+            }
+            *n += 1;
+        }
+    });
+
+    slugs
+}
+
+/// The anchor GitHub generates for a heading: lowercase, with punctuation removed and spaces
+/// replaced by `-`.
+///
+/// A repeated heading gets a suffix `-1`, `-2`, ..., which [`heading_slugs`] adds.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The markdown files in `paths`, searching directories recursively and skipping hidden ones.
+pub fn markdown_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            collect_markdown(path, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn collect_markdown(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read dir: {}", dir.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_markdown(&path, files)?;
+        } else if is_markdown(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The root of the git repository `dir` is in: the nearest ancestor with a `.git`.
+pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+    let dir = fs::canonicalize(dir).ok()?;
+    dir.ancestors()
+        .find(|d| d.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+}
+
+fn cache_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Hello, World!"), "hello-world");
+        assert_eq!(slug("  `xpmd check` options "), "xpmd-check-options");
+        assert_eq!(slug("a_b - c"), "a_b---c");
+        assert_eq!(slug("中文 标题"), "中文-标题");
+    }
+
+    #[test]
+    fn test_anchors() {
+        let root = md::parse("# Intro\n\n## Intro\n\n## *Usage* it\n\n<a id=\"x\"></a>\n").unwrap();
+        let mut got: Vec<_> = anchors(&root).into_iter().collect();
+        got.sort();
+        assert_eq!(got, vec!["intro", "intro-1", "usage-it", "x"]);
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        fs::create_dir_all(d.join("img")).unwrap();
+        fs::write(d.join("img/a.png"), "").unwrap();
+        fs::write(d.join("img/a.txt"), "").unwrap();
+        fs::write(d.join("b.md"), "# Setup\n").unwrap();
+
+        let md = [
+            "# Top",
+            "",
+            "[ok](b.md) [ok](b.md#setup) [ok](#top) [ok](https://x.org/a.md)",
+            "[no](c.md) [no](b.md#install) [no](#bottom)",
+            "",
+            "![ok](img/a.png) ![no](img/a.txt) ![no](img/b.png) ![ref][t]",
+            "",
+            "[t]: img/a.txt",
+            "",
+        ]
+        .join("\n");
+
+        let path = d.join("a.md");
+        let issues = Checker::new().check_markdown(&md, &path).unwrap();
+        let got: Vec<_> = issues
+            .iter()
+            .map(|i| (i.line, i.column, i.url.as_str(), i.kind))
+            .collect();
+
+        assert_eq!(got, vec![
+            (4, 1, "c.md", IssueKind::MissingFile),
+            (4, 12, "b.md#install", IssueKind::MissingAnchor),
+            (4, 31, "#bottom", IssueKind::MissingAnchor),
+            (6, 18, "img/a.txt", IssueKind::NotAnImage),
+            (6, 35, "img/b.png", IssueKind::MissingFile),
+            (8, 1, "img/a.txt", IssueKind::NotAnImage),
+        ]);
+
+        assert_eq!(
+            issues[0].to_string(),
+            format!("{}:4:1: missing-file: c.md", path.display())
+        );
+    }
+
+    #[test]
+    fn test_check_root_relative() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        fs::create_dir_all(d.join("docs/sub")).unwrap();
+        fs::write(d.join("docs/b.md"), "# Setup\n").unwrap();
+
+        let md = "[ok](/docs/b.md#setup) [no](/docs/c.md) [no](/docs/b.md#install)\n";
+        let path = d.join("docs/sub/a.md");
+
+        // Without a root, links from the root are not checked.
+        assert_eq!(Checker::new().check_markdown(md, &path).unwrap(), vec![]);
+
+        let issues = Checker::new()
+            .with_root(d)
+            .check_markdown(md, &path)
+            .unwrap();
+        let got: Vec<_> = issues.iter().map(|i| (i.url.as_str(), i.kind)).collect();
+        assert_eq!(got, vec![
+            ("/docs/c.md", IssueKind::MissingFile),
+            ("/docs/b.md#install", IssueKind::MissingAnchor),
+        ]);
+    }
+
+    #[test]
+    fn test_check_unreadable() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        fs::write(d.join("bin.md"), b"# \xff\xfe\n").unwrap();
+
+        // A target that is not utf-8 is reported, and the links after it are still checked.
+        let md = "[no](bin.md#a) [no](bin.md#b)\n\n[no](c.md)\n";
+        let path = d.join("a.md");
+        fs::write(&path, md).unwrap();
+        let issues = Checker::new().check_file(&path).unwrap();
+        let got: Vec<_> = issues
+            .iter()
+            .map(|i| (i.line, i.column, i.url.as_str(), i.kind))
+            .collect();
+        assert_eq!(got, vec![
+            (1, 1, "bin.md#a", IssueKind::Unreadable),
+            (1, 16, "bin.md#b", IssueKind::Unreadable),
+            (3, 1, "c.md", IssueKind::MissingFile),
+        ]);
+
+        let issues = Checker::new().check_file(&d.join("bin.md")).unwrap();
+        assert_eq!(
+            issues[0].to_string(),
+            format!("{0}:1:1: unreadable: {0}", d.join("bin.md").display())
+        );
+    }
+
+    #[test]
+    fn test_markdown_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let d = dir.path();
+        fs::create_dir_all(d.join("sub")).unwrap();
+        fs::create_dir_all(d.join(".git")).unwrap();
+        fs::write(d.join("b.md"), "").unwrap();
+        fs::write(d.join("sub/a.markdown"), "").unwrap();
+        fs::write(d.join("sub/c.txt"), "").unwrap();
+        fs::write(d.join(".git/d.md"), "").unwrap();
+
+        let files = markdown_files(&[d.to_path_buf()]).unwrap();
+        assert_eq!(files, vec![d.join("b.md"), d.join("sub/a.markdown")]);
+    }
+}
@@ -0,0 +1,234 @@
+//! Project settings from an `xpmd.toml`, with named profiles:
+//!
+//! ```toml
+//! width = 800
+//! css = ["theme.css"]
+//! chrome = "/opt/chrome/chrome"
+//!
+//! [profile.retina]
+//! scale = 2
+//!
+//! [profile.zhihu]
+//! format = "jpg"
+//! width = 690
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::render::batch::RenderOptions;
+
+/// The file name of a project config.
+pub const CONFIG_FILE: &str = "xpmd.toml";
+
+/// Render settings; `None` is not set, and falls back to a less specific setting.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Output format, one of [`crate::render::OUTPUT_FORMATS`].
+    pub format: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+
+    /// Device pixels per css pixel.
+    pub scale: Option<f64>,
+
+    /// Base path for assets.
+    pub base: Option<PathBuf>,
+
+    /// Stylesheets added to html and markdown pages.
+    pub css: Option<Vec<PathBuf>>,
+
+    /// The Chrome executable, instead of searching for one.
+    pub chrome: Option<PathBuf>,
+}
+
+impl Settings {
+    /// These settings, with those of `over` replacing them where `over` sets them.
+    pub fn merge(self, over: Settings) -> Settings {
+        Settings {
+            format: over.format.or(self.format),
+            width: over.width.or(self.width),
+            height: over.height.or(self.height),
+            scale: over.scale.or(self.scale),
+            base: over.base.or(self.base),
+            css: over.css.or(self.css),
+            chrome: over.chrome.or(self.chrome),
+        }
+    }
+
+    /// Render options from these settings, and the defaults for what they do not set.
+    pub fn render_options(&self) -> RenderOptions {
+        let defaults = RenderOptions::default();
+        RenderOptions {
+            format: self.format.clone().unwrap_or(defaults.format),
+            width: self.width.unwrap_or(defaults.width),
+            height: self.height.unwrap_or(defaults.height),
+            scale: self.scale.unwrap_or(defaults.scale),
+            base: self.base.clone(),
+            css: self.css.clone().unwrap_or_default(),
+            ..defaults
+        }
+    }
+
+    /// Resolve relative paths against `dir`, the directory of the config file.
+    fn resolve_paths(&mut self, dir: &Path) {
+        if let Some(base) = &mut self.base {
+            *base = dir.join(&*base);
+        }
+        for css in self.css.iter_mut().flatten() {
+            *css = dir.join(&*css);
+        }
+        // A bare name such as "chromium" is looked up in PATH.
+        if let Some(chrome) = &mut self.chrome {
+            if chrome.components().count() > 1 {
+                *chrome = dir.join(&*chrome);
+            }
+        }
+    }
+}
+
+/// The settings of an `xpmd.toml`: defaults at the top level, and profiles in
+/// `[profile.<name>]` tables that override them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub defaults: Settings,
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl Config {
+    /// Parse the content of an `xpmd.toml`.
+    pub fn parse(toml_str: &str) -> anyhow::Result<Self> {
+        let mut table: toml::Table = toml::from_str(toml_str)?;
+
+        let profiles = match table.remove("profile") {
+            Some(profiles) => profiles.try_into().context("Invalid [profile] table")?,
+            None => BTreeMap::new(),
+        };
+        let defaults = toml::Value::Table(table).try_into()?;
+
+        Ok(Self { defaults, profiles })
+    }
+
+    /// Load a config file. Relative paths in it are relative to the file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {}", path.display()))?;
+        let mut config =
+            Self::parse(&s).with_context(|| format!("Invalid config: {}", path.display()))?;
+
+        let path = std::path::absolute(path)?;
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        config.defaults.resolve_paths(dir);
+        for settings in config.profiles.values_mut() {
+            settings.resolve_paths(dir);
+        }
+        Ok(config)
+    }
+
+    /// Find the `xpmd.toml` in `dir` or the nearest of its parents.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|d| d.join(CONFIG_FILE))
+            .find(|p| p.is_file())
+    }
+
+    /// The defaults, overridden by a profile if one is given.
+    pub fn settings(&self, profile: Option<&str>) -> anyhow::Result<Settings> {
+        let Some(name) = profile else {
+            return Ok(self.defaults.clone());
+        };
+
+        let Some(settings) = self.profiles.get(name) else {
+            let names = self.profiles.keys().cloned().collect::<Vec<_>>();
+            anyhow::bail!(
+                "Unknown profile: {}. Defined: {}",
+                name,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            );
+        };
+        Ok(self.defaults.clone().merge(settings.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+width = 800
+base = "assets"
+css = ["theme.css"]
+chrome = "chromium"
+
+[profile.retina]
+scale = 2
+
+[profile.zhihu]
+format = "jpg"
+width = 690
+css = []
+"#;
+
+    #[test]
+    fn test_settings() {
+        let config = Config::parse(TOML).unwrap();
+
+        let zhihu = config.settings(Some("zhihu")).unwrap();
+        assert_eq!(zhihu.format.as_deref(), Some("jpg"));
+        assert_eq!(zhihu.width, Some(690));
+        assert_eq!(zhihu.css, Some(vec![]));
+        assert_eq!(zhihu.base, Some(PathBuf::from("assets")));
+
+        let options = config.settings(Some("retina")).unwrap().render_options();
+        assert_eq!((options.width, options.height), (800, 2000));
+        assert_eq!(options.scale, 2.0);
+        assert_eq!(options.css, vec![PathBuf::from("theme.css")]);
+
+        // Command line flags override the config.
+        let cli = Settings {
+            width: Some(1200),
+            ..Settings::default()
+        };
+        assert_eq!(config.settings(None).unwrap().merge(cli).width, Some(1200));
+
+        let err = config.settings(Some("nope")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile: nope. Defined: retina, zhihu"
+        );
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        assert!(Config::parse("widht = 800\n").is_err());
+        assert!(Config::parse("[profile.x]\nwidht = 800\n").is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("docs")).unwrap();
+        fs::write(tmp.path().join(CONFIG_FILE), TOML).unwrap();
+
+        let path = Config::find(&tmp.path().join("docs")).unwrap();
+        assert_eq!(path, tmp.path().join(CONFIG_FILE));
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.defaults.base, Some(tmp.path().join("assets")));
+        assert_eq!(
+            config.defaults.css,
+            Some(vec![tmp.path().join("theme.css")])
+        );
+        assert_eq!(config.defaults.chrome, Some(PathBuf::from("chromium")));
+    }
+}
@@ -0,0 +1,211 @@
+//! Check that the programs and fonts rendering needs are installed, with a hint to fix what
+//! is missing on this OS.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::render::with_chrome::WithChrome;
+
+/// The result of a check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+
+    /// What was found if it passed, or what is wrong.
+    pub result: Result<String, String>,
+
+    /// How to fix it on this OS, if it failed.
+    pub hint: Option<&'static str>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: anyhow::Result<String>, hint: &'static str) -> Self {
+        let result = result.map_err(|e| format!("{:#}", e));
+        let hint = result.is_err().then_some(hint);
+        Self { name, result, hint }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(found) => write!(f, "✅ {:<12} {}", self.name, found)?,
+            Err(e) => write!(f, "❌ {:<12} {}", self.name, e)?,
+        }
+        if let Some(hint) = self.hint {
+            write!(f, "\n   fix: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// The operating systems with their own fix hints, by `std::env::consts::OS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    MacOs,
+    Linux,
+    Windows,
+}
+
+impl Os {
+    pub fn current() -> Self {
+        match std::env::consts::OS {
+            "macos" => Os::MacOs,
+            "windows" => Os::Windows,
+            _ => Os::Linux,
+        }
+    }
+
+    pub fn chrome_hint(&self) -> &'static str {
+        match self {
+            Os::MacOs => "install Chrome from https://www.google.com/chrome/",
+            Os::Linux => {
+                "sudo apt install chromium (Debian/Ubuntu) or sudo dnf install chromium (Fedora)"
+            }
+            Os::Windows => "install Chrome from https://www.google.com/chrome/",
+        }
+    }
+
+    pub fn image_magick_hint(&self) -> &'static str {
+        match self {
+            Os::MacOs => "brew install imagemagick",
+            Os::Linux => "sudo apt install imagemagick (Debian/Ubuntu) or sudo dnf install ImageMagick (Fedora)",
+            Os::Windows => "install from https://imagemagick.org/script/download.php#windows",
+        }
+    }
+
+    pub fn cjk_font_hint(&self) -> &'static str {
+        match self {
+            Os::MacOs => "CJK fonts come with macOS; reinstall them in Font Book",
+            Os::Linux => "sudo apt install fonts-noto-cjk (Debian/Ubuntu) or sudo dnf install google-noto-sans-cjk-fonts (Fedora)",
+            Os::Windows => "add a Chinese language pack in Settings > Time & Language",
+        }
+    }
+
+    pub fn emoji_font_hint(&self) -> &'static str {
+        match self {
+            Os::MacOs => "Apple Color Emoji comes with macOS; reinstall it in Font Book",
+            Os::Linux => "sudo apt install fonts-noto-color-emoji (Debian/Ubuntu) or sudo dnf install google-noto-emoji-color-fonts (Fedora)",
+            Os::Windows => "Segoe UI Emoji comes with Windows 10 and later",
+        }
+    }
+
+    pub fn temp_dir_hint(&self) -> &'static str {
+        match self {
+            Os::Windows => "set TEMP to a writable directory",
+            _ => "set TMPDIR to a writable directory",
+        }
+    }
+
+    /// Font files that come with the OS, if fontconfig is not there to ask.
+    fn font_files(&self, emoji: bool) -> &'static [&'static str] {
+        match (self, emoji) {
+            (Os::MacOs, false) => &[
+                "/System/Library/Fonts/PingFang.ttc",
+                "/System/Library/Fonts/Hiragino Sans GB.ttc",
+            ],
+            (Os::MacOs, true) => &["/System/Library/Fonts/Apple Color Emoji.ttc"],
+            (Os::Windows, false) => &[
+                "C:\\Windows\\Fonts\\msyh.ttc",
+                "C:\\Windows\\Fonts\\simsun.ttc",
+            ],
+            (Os::Windows, true) => &["C:\\Windows\\Fonts\\seguiemj.ttf"],
+            (Os::Linux, _) => &[],
+        }
+    }
+}
+
+/// Run every check, in the order to fix them.
+pub async fn run() -> Vec<Check> {
+    let os = Os::current();
+
+    vec![
+        Check::new("chrome", chrome(), os.chrome_hint()),
+        Check::new("imagemagick", image_magick(), os.image_magick_hint()),
+        Check::new("tempdir", temp_dir(), os.temp_dir_hint()),
+        Check::new(
+            "render",
+            render().await,
+            "fix the checks above; if they pass, run `xpmd render` to see the error",
+        ),
+        Check::new("cjk font", font(os, false), os.cjk_font_hint()),
+        Check::new("emoji font", font(os, true), os.emoji_font_hint()),
+    ]
+}
+
+fn chrome() -> anyhow::Result<String> {
+    let path = WithChrome::find_chrome_executable()?;
+    Ok(format!("{} ({})", path, WithChrome::chrome_version()?))
+}
+
+fn image_magick() -> anyhow::Result<String> {
+    let path = WithChrome::find_image_magick()?;
+    Ok(format!(
+        "{} ({})",
+        path,
+        WithChrome::command_version(&path, "-version")?
+    ))
+}
+
+fn temp_dir() -> anyhow::Result<String> {
+    let dir = tempfile::TempDir::new()?;
+    fs::write(dir.path().join("probe"), "xpmd")?;
+    Ok(format!("{} is writable", std::env::temp_dir().display()))
+}
+
+/// Render a trivial page to png.
+async fn render() -> anyhow::Result<String> {
+    let data =
+        WithChrome::render_markup("text/html", "<p>xpmd</p>", "png", None, None, None).await?;
+    if !data.starts_with(b"\x89PNG") {
+        anyhow::bail!("the output is not a png");
+    }
+    Ok(format!("html to png: {} bytes", data.len()))
+}
+
+/// Find a font for CJK text, or for emoji, with fontconfig or among the OS fonts.
+fn font(os: Os, emoji: bool) -> anyhow::Result<String> {
+    // "und-zsye" is the language of emoji fonts; a plain font with a few monochrome emoji,
+    // such as DejaVu Sans, does not claim it.
+    let pattern = if emoji { ":lang=und-zsye" } else { ":lang=zh" };
+    if let Ok(output) = Command::new("fc-list").args([pattern, "family"]).output() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Some(family) = stdout.lines().find(|l| !l.trim().is_empty()) {
+            return Ok(family.trim().to_string());
+        }
+    }
+
+    os.font_files(emoji)
+        .iter()
+        .find(|f| Path::new(f).exists())
+        .map(|f| f.to_string())
+        .ok_or_else(|| anyhow::anyhow!("no font found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_display() {
+        let passed = Check::new("tempdir", temp_dir(), Os::Linux.temp_dir_hint());
+        assert!(passed.passed());
+        assert_eq!(passed.hint, None);
+
+        let failed = Check::new(
+            "imagemagick",
+            Err(anyhow::anyhow!("not found")),
+            Os::MacOs.image_magick_hint(),
+        );
+        assert_eq!(
+            failed.to_string(),
+            "❌ imagemagick  not found\n   fix: brew install imagemagick"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::Mime;
+
+/// Number of hex digits of the sha256 used in file names.
+const HASH_PREFIX_LEN: usize = 16;
+
+/// Stores assets in a directory under content-hashed names, such as `3f2a9c0e5b7d1a4c.png`.
+///
+/// Identical files are stored once, no matter how many documents refer to them.
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    dir: PathBuf,
+}
+
+impl AssetStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store `data` with the file suffix `suffix` and return the file name.
+    pub fn put(&self, data: &[u8], suffix: &str) -> anyhow::Result<String> {
+        let hash = format!("{:x}", Sha256::digest(data));
+        let file_name = format!("{}.{}", &hash[..HASH_PREFIX_LEN], suffix);
+        let path = self.dir.join(&file_name);
+
+        if path.exists() {
+            return Ok(file_name);
+        }
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create asset dir: {}", self.dir.display()))?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write asset: {}", path.display()))?;
+
+        Ok(file_name)
+    }
+
+    /// Copy a file into the store and return the file name.
+    ///
+    /// The suffix is chosen by the content if it is a known image type, otherwise by the
+    /// extension of `path`.
+    pub fn put_file(&self, path: &Path) -> anyhow::Result<String> {
+        let data =
+            fs::read(path).with_context(|| format!("Failed to read asset: {}", path.display()))?;
+
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let suffix = Mime::sniff(&data)
+            .filter(|mime| mime.starts_with("image/"))
+            .or_else(|| Mime::get(&ext))
+            .and_then(Mime::get_suffix)
+            .filter(|suffix| !suffix.is_empty())
+            .unwrap_or(&ext);
+
+        self.put(&data, suffix)
+    }
+}
+
+/// Whether a url in markdown refers to a local file.
+pub(crate) fn is_local(url: &str) -> bool {
+    let has_scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| {
+            scheme.len() > 1
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+        .unwrap_or(false);
+
+    !(url.is_empty() || url.starts_with('#') || url.starts_with("//") || has_scheme)
+}
+
+/// Resolve a local url against `base_dir`, trying the percent-decoded form if it does not
+/// exist as is.
+pub(crate) fn resolve_local(base_dir: &Path, url: &str) -> PathBuf {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+
+    let path = base_dir.join(url);
+    if path.exists() {
+        return path;
+    }
+
+    let decoded = percent_encoding::percent_decode_str(url).decode_utf8_lossy();
+    base_dir.join(decoded.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000";
+
+    #[test]
+    fn test_put_dedup() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path().join("assets"));
+
+        let a = store.put(b"abc", "txt").unwrap();
+        let b = store.put(b"abc", "txt").unwrap();
+        let c = store.put(b"abd", "txt").unwrap();
+
+        assert_eq!(a, "ba7816bf8f01cfea.txt");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(fs::read_dir(store.dir()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_put_file_sniff() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path().join("assets"));
+
+        // The content wins over a misleading extension.
+        let path = dir.path().join("pic.JPEG");
+        fs::write(&path, PNG).unwrap();
+        assert!(store.put_file(&path).unwrap().ends_with(".png"));
+
+        // Unknown content falls back to the normalized extension.
+        let path = dir.path().join("pic.jpeg");
+        fs::write(&path, b"not an image").unwrap();
+        assert!(store.put_file(&path).unwrap().ends_with(".jpg"));
+
+        let path = dir.path().join("diagram.svg");
+        fs::write(&path, "<?xml version=\"1.0\"?>\n<svg></svg>").unwrap();
+        assert!(store.put_file(&path).unwrap().ends_with(".svg"));
+
+        assert!(store.put_file(&dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_is_local() {
+        assert!(is_local("a.png"));
+        assert!(is_local("../img/a b.png"));
+        assert!(is_local("/abs/a.png"));
+        assert!(is_local("C:/a.png"));
+
+        assert!(!is_local(""));
+        assert!(!is_local("#anchor"));
+        assert!(!is_local("https://a.com/a.png"));
+        assert!(!is_local("//a.com/a.png"));
+        assert!(!is_local("data:image/png;base64,AAAA"));
+    }
+
+    #[test]
+    fn test_resolve_local() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a b.png"), PNG).unwrap();
+
+        assert_eq!(
+            resolve_local(dir.path(), "a%20b.png?x=1"),
+            dir.path().join("a b.png")
+        );
+        assert_eq!(
+            resolve_local(dir.path(), "a b.png#frag"),
+            dir.path().join("a b.png")
+        );
+    }
+}
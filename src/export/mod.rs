@@ -0,0 +1,661 @@
+pub mod assets;
+pub mod publish;
+pub mod wechat;
+pub mod zhihu;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context;
+use markdown::mdast::Node;
+
+use crate::export::assets::is_local;
+use crate::export::assets::resolve_local;
+use crate::export::assets::AssetStore;
+use crate::md;
+use crate::md::math::Math;
+use crate::md::splice::node_source;
+use crate::md::splice::Splice;
+use crate::md::transform::admonition;
+use crate::md::transform::github_alert;
+use crate::md::transform::is_admonition_body;
+use crate::md::transform::ConvertFootnotes;
+use crate::md::transform::EmbedImages;
+use crate::md::transform::FootnoteStyle;
+use crate::md::transform::Pipeline;
+use crate::md::Graphviz;
+use crate::md::MarkdownToHtml;
+use crate::md::MathOutput;
+use crate::md::Mermaid;
+use crate::render::github_markdown_page;
+use crate::render::with_chrome::WithChrome;
+use crate::Mime;
+
+/// A publishing platform that markdown is exported for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Zhihu,
+    Wechat,
+}
+
+impl Target {
+    /// Build the export settings of this target.
+    ///
+    /// # Arguments
+    ///
+    /// * `asset_dir` - the directory to write rendered images to
+    /// * `asset_url` - the url of `asset_dir` in the output markdown, such as "assets"
+    pub fn export(&self, asset_dir: impl Into<PathBuf>, asset_url: impl Into<String>) -> Export {
+        match self {
+            Target::Zhihu => Export::zhihu(asset_dir, asset_url),
+            Target::Wechat => Export::wechat(asset_dir, asset_url),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zhihu" => Ok(Target::Zhihu),
+            "wechat" => Ok(Target::Wechat),
+            _ => anyhow::bail!("Unsupported export target: {}. Supported: zhihu, wechat", s),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Zhihu => write!(f, "zhihu"),
+            Target::Wechat => write!(f, "wechat"),
+        }
+    }
+}
+
+/// Markdown constructs that a target can not display and are replaced with images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// Inline and display formulas.
+    Math,
+    Table,
+    /// ```` ```mermaid ```` code blocks.
+    Mermaid,
+    /// ```` ```dot ```` and ```` ```graphviz ```` code blocks.
+    Dot,
+    /// Complex code blocks, see [`Export::max_code_width`].
+    Code,
+    /// GitHub alerts such as `> [!NOTE]`, and MkDocs admonitions such as `!!! note`.
+    Alert,
+}
+
+impl Feature {
+    /// The name used in image file names and alt texts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Math => "math",
+            Feature::Table => "table",
+            Feature::Mermaid => "mermaid",
+            Feature::Dot => "dot",
+            Feature::Code => "code",
+            Feature::Alert => "alert",
+        }
+    }
+}
+
+impl FromStr for Feature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "math" => Ok(Feature::Math),
+            "table" => Ok(Feature::Table),
+            "mermaid" => Ok(Feature::Mermaid),
+            "dot" => Ok(Feature::Dot),
+            "code" => Ok(Feature::Code),
+            "alert" => Ok(Feature::Alert),
+            _ => anyhow::bail!(
+                "Unknown feature: {}. Supported: math, table, mermaid, dot, code, alert",
+                s
+            ),
+        }
+    }
+}
+
+/// The document format an export produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Markdown,
+
+    /// Html with every style inlined, for editors that strip `<style>` and classes.
+    InlineStyledHtml,
+}
+
+/// How a collected node is replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Image(Feature),
+    /// Copy a local image into the asset dir, see [`Export::source_dir`].
+    Asset,
+    /// The indented body of a MkDocs admonition, which is replaced with it.
+    AdmonitionBody,
+}
+
+/// Export markdown for a publishing platform: constructs the platform can not display are
+/// rendered to images, and the markdown is rewritten to link to the images.
+///
+/// Images are stored in `asset_dir` under content-hashed names, see [`AssetStore`].
+#[derive(Debug, Clone)]
+pub struct Export {
+    /// Constructs to replace with images.
+    pub features: BTreeSet<Feature>,
+
+    /// The directory to write rendered and collected images to.
+    pub asset_dir: PathBuf,
+
+    /// The url of `asset_dir` in the output markdown, such as "assets". Empty for no prefix.
+    ///
+    /// If it has a `{path}`, such as a [`GitPublisher::asset_url`], the file name is put there
+    /// instead of being appended.
+    ///
+    /// [`GitPublisher::asset_url`]: crate::export::publish::GitPublisher::asset_url
+    pub asset_url: String,
+
+    /// Output image type, such as "png" or "jpg".
+    pub image_format: String,
+
+    /// A code block is complex if it has a line wider than this, or has box drawing characters.
+    /// The platforms wrap such lines and break ascii diagrams.
+    pub max_code_width: usize,
+
+    /// How footnotes are written. [`FootnoteStyle::LinkReferences`] also replaces external
+    /// links with their text and a reference number, for platforms that block them.
+    pub footnotes: FootnoteStyle,
+
+    pub output: Output,
+
+    /// The directory of the input document. If set, local images the document refers to are
+    /// copied into `asset_dir`, and relative urls in rendered tables are resolved against it.
+    pub source_dir: Option<PathBuf>,
+
+    pub mermaid: Mermaid,
+
+    pub dot: Graphviz,
+}
+
+impl Export {
+    /// Export settings that replace nothing; use a [`Target`] to build a useful one.
+    pub fn new(asset_dir: impl Into<PathBuf>, asset_url: impl Into<String>) -> Self {
+        Self {
+            features: BTreeSet::new(),
+            asset_dir: asset_dir.into(),
+            asset_url: asset_url.into(),
+            image_format: "png".to_string(),
+            max_code_width: 80,
+            footnotes: FootnoteStyle::Keep,
+            output: Output::Markdown,
+            source_dir: None,
+            mermaid: Mermaid::default(),
+            dot: Graphviz::default(),
+        }
+    }
+
+    /// Export markdown source, write images into `asset_dir` and return the new document.
+    pub async fn run(&self, md: &str) -> anyhow::Result<String> {
+        let md = self.rewrite(md).await?;
+        let md = self.convert_footnotes(&md).await?;
+
+        match self.output {
+            Output::Markdown => Ok(md),
+            Output::InlineStyledHtml => {
+                let html = MarkdownToHtml::new()
+                    .with_math(MathOutput::MathMl)
+                    .pipeline()
+                    .with(self.embed_images())
+                    .run(&md)
+                    .await?;
+                wechat::inline_css(&html).await
+            }
+        }
+    }
+
+    /// Replace nodes with images, return the new markdown.
+    async fn rewrite(&self, md: &str) -> anyhow::Result<String> {
+        let root = md::parse(md)?;
+
+        // Definitions used by image references, such as `![a][logo]`, point to images.
+        let mut image_refs = BTreeSet::new();
+        md::walk(&root, &mut |node| {
+            if let Node::ImageReference(r) = node {
+                image_refs.insert(r.identifier.as_str());
+            }
+        });
+
+        let mut nodes = vec![];
+        self.collect(&root, &image_refs, &mut nodes);
+
+        let store = AssetStore::new(&self.asset_dir);
+        let mut splice = Splice::new(md);
+
+        let mut nodes = nodes.into_iter().peekable();
+        while let Some((action, node)) = nodes.next() {
+            let Some(position) = node.position() else {
+                continue;
+            };
+
+            let mut position = position.clone();
+            if let Some((_, body)) = nodes.next_if(|(a, _)| *a == Action::AdmonitionBody) {
+                if let Some(p) = body.position() {
+                    position.end = p.end.clone();
+                }
+            }
+            let position = &position;
+
+            let replacement = match (action, node) {
+                (Action::Image(feature), _) => {
+                    let data = self
+                        .render(feature, node, &node_source(md, position))
+                        .await?;
+
+                    let suffix = Mime::get(&self.image_format)
+                        .and_then(Mime::get_suffix)
+                        .unwrap_or(&self.image_format);
+                    let file_name = store.put(&data, suffix)?;
+
+                    let alt = match node {
+                        Node::InlineMath(m) => &m.value,
+                        Node::Math(m) => &m.value,
+                        _ => feature.name(),
+                    };
+                    format!("![{}]({})", escape_alt(alt), self.asset_link(&file_name))
+                }
+                (Action::Asset, Node::Image(image)) => {
+                    let file_name = self.collect_asset(&store, &image.url, position.start.line)?;
+                    format!(
+                        "![{}]({}{})",
+                        escape_alt(&image.alt),
+                        self.asset_link(&file_name),
+                        title_suffix(image.title.as_deref())
+                    )
+                }
+                (Action::Asset, Node::Definition(def)) => {
+                    let file_name = self.collect_asset(&store, &def.url, position.start.line)?;
+                    format!(
+                        "[{}]: {}{}",
+                        def.label.as_deref().unwrap_or(&def.identifier),
+                        self.asset_link(&file_name),
+                        title_suffix(def.title.as_deref())
+                    )
+                }
+                _ => unreachable!("unexpected node for {:?}", action),
+            };
+
+            splice.replace(position, replacement);
+        }
+
+        splice.apply()
+    }
+
+    /// Convert footnotes as [`Export::footnotes`] says, changing only the lines they are on.
+    async fn convert_footnotes(&self, md: &str) -> anyhow::Result<String> {
+        if self.footnotes == FootnoteStyle::Keep {
+            return Ok(md.to_string());
+        }
+
+        let root = Pipeline::new()
+            .with_dangerous_html(true)
+            .with(ConvertFootnotes::new(self.footnotes))
+            .parse(md)
+            .await?;
+        md::to_markdown_preserving(&root, md)
+    }
+
+    /// Copy the local image at `url` into the store and return its file name.
+    fn collect_asset(&self, store: &AssetStore, url: &str, line: usize) -> anyhow::Result<String> {
+        let source_dir = self.source_dir.as_deref().unwrap_or(Path::new(""));
+        let path = resolve_local(source_dir, url);
+
+        store
+            .put_file(&path)
+            .with_context(|| format!("Failed to collect image {} at line {}", url, line))
+    }
+
+    /// Collect nodes to replace in document order. Descendants of a collected node are skipped
+    /// since they are part of its replacement.
+    fn collect<'a>(
+        &self,
+        node: &'a Node,
+        image_refs: &BTreeSet<&str>,
+        nodes: &mut Vec<(Action, &'a Node)>,
+    ) {
+        if let Some(feature) = self.feature_of(node) {
+            if self.features.contains(&feature) {
+                nodes.push((Action::Image(feature), node));
+                return;
+            }
+        }
+
+        if self.source_dir.is_some() {
+            match node {
+                Node::Image(image) if is_local(&image.url) => {
+                    nodes.push((Action::Asset, node));
+                    return;
+                }
+                Node::Definition(def)
+                    if image_refs.contains(def.identifier.as_str()) && is_local(&def.url) =>
+                {
+                    nodes.push((Action::Asset, node));
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(children) = node.children() {
+            let mut children = children.iter().peekable();
+            while let Some(child) = children.next() {
+                self.collect(child, image_refs, nodes);
+
+                let collected = nodes.last().is_some_and(|(_, n)| std::ptr::eq(*n, child));
+                if collected && admonition(child).is_some() {
+                    if let Some(body) = children.next_if(|n| is_admonition_body(n)) {
+                        nodes.push((Action::AdmonitionBody, body));
+                    }
+                }
+            }
+        }
+    }
+
+    fn feature_of(&self, node: &Node) -> Option<Feature> {
+        match node {
+            Node::InlineMath(_) | Node::Math(_) => Some(Feature::Math),
+            Node::Table(_) => Some(Feature::Table),
+            Node::Code(code) => match code.lang.as_deref() {
+                Some("mermaid") => Some(Feature::Mermaid),
+                Some("dot") | Some("graphviz") => Some(Feature::Dot),
+                _ if self.is_complex_code(&code.value) => Some(Feature::Code),
+                _ => None,
+            },
+            _ if github_alert(node).is_some() || admonition(node).is_some() => Some(Feature::Alert),
+            _ => None,
+        }
+    }
+
+    fn is_complex_code(&self, code: &str) -> bool {
+        code.lines().any(|line| {
+            line.chars().count() > self.max_code_width
+                || line.chars().any(|c| ('\u{2500}'..='\u{257F}').contains(&c))
+        })
+    }
+
+    /// Render a node to image data. `source` is the markdown source of the node.
+    async fn render(&self, feature: Feature, node: &Node, source: &str) -> anyhow::Result<Vec<u8>> {
+        let line = node.position().map(|p| p.start.line).unwrap_or_default();
+        let format = &self.image_format;
+
+        match (feature, node) {
+            (Feature::Math, Node::InlineMath(m)) => Math::to_image(&m.value, false, format).await,
+            (Feature::Math, Node::Math(m)) => Math::to_image(&m.value, true, format).await,
+            (Feature::Mermaid, Node::Code(code)) => {
+                self.mermaid.to_image(&code.value, line, format).await
+            }
+            (Feature::Dot, Node::Code(code)) => self.dot.to_image(&code.value, line, format).await,
+            _ => {
+                // Tables, code blocks and alerts are rendered as GitHub does.
+                let html = MarkdownToHtml::new()
+                    .with_math(MathOutput::MathMl)
+                    .render(source)
+                    .await?;
+                let page = github_markdown_page(&html);
+                let asset_base = self.source_dir.as_deref();
+                WithChrome::render_markup("text/html", &page, format, None, None, asset_base)
+                    .await
+                    .with_context(|| {
+                        format!("Failed to render {} at line {}", feature.name(), line)
+                    })
+            }
+        }
+    }
+
+    /// Embed the local images of the exported markdown, since an editor the html is pasted
+    /// into can not load them. Published images have remote urls and are kept.
+    fn embed_images(&self) -> EmbedImages {
+        let source_dir = self.source_dir.clone().unwrap_or_default();
+        if self.asset_url.contains("{path}") {
+            return EmbedImages::new(source_dir);
+        }
+
+        // Images in the asset dir are linked as `{asset_url}/{file_name}`.
+        let prefix = self.asset_link("");
+        if prefix.is_empty() {
+            EmbedImages::new(&self.asset_dir)
+        } else {
+            EmbedImages::new(source_dir).with_prefix(prefix, &self.asset_dir)
+        }
+    }
+
+    fn asset_link(&self, file_name: &str) -> String {
+        if self.asset_url.contains("{path}") {
+            return self.asset_url.replace("{path}", file_name);
+        }
+
+        let base = self.asset_url.trim_end_matches('/');
+        if base.is_empty() {
+            file_name.to_string()
+        } else {
+            format!("{}/{}", base, file_name)
+        }
+    }
+}
+
+/// Build the ` "title"` part of a markdown link or image, or "" if there is no title.
+fn title_suffix(title: Option<&str>) -> String {
+    match title {
+        Some(title) => format!(" \"{}\"", title.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => String::new(),
+    }
+}
+
+/// Escape text to put in the alt of a markdown image, in a single line.
+fn escape_alt(alt: &str) -> String {
+    let mut escaped = String::with_capacity(alt.len());
+    for c in alt.chars() {
+        match c {
+            '\\' | '[' | ']' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_from_str() {
+        assert_eq!("zhihu".parse::<Target>().unwrap(), Target::Zhihu);
+        assert_eq!("wechat".parse::<Target>().unwrap(), Target::Wechat);
+        assert_eq!(Target::Zhihu.to_string(), "zhihu");
+        assert!("foo".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn test_collect() {
+        let export = Export::zhihu("assets", "assets");
+        let md = "$a$\n\n| x |\n| - |\n| $b$ |\n\n```rust\nshort\n```\n\n```\n┌─┐\n```\n\n```mermaid\ngraph TD\n```\n\n> [!TIP]\n> $c$\n\n!!! note\n\n    x\n";
+        let root = md::parse(md).unwrap();
+
+        let mut nodes = vec![];
+        export.collect(&root, &BTreeSet::new(), &mut nodes);
+
+        let actions: Vec<_> = nodes.iter().map(|(a, _)| *a).collect();
+        assert_eq!(actions, vec![
+            Action::Image(Feature::Math),
+            Action::Image(Feature::Table),
+            Action::Image(Feature::Code),
+            Action::Image(Feature::Mermaid),
+            Action::Image(Feature::Alert),
+            Action::Image(Feature::Alert),
+            Action::AdmonitionBody,
+        ]);
+
+        // A fenced block after a marker is not its body.
+        let root = md::parse("!!! note\n\n```\nx\n```\n").unwrap();
+        let mut nodes = vec![];
+        export.collect(&root, &BTreeSet::new(), &mut nodes);
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_embed_images() {
+        let mut export = Export::wechat("out/assets", "assets");
+        export.source_dir = Some(PathBuf::from("src"));
+        let embed = export.embed_images();
+        assert_eq!(embed.base_dir, PathBuf::from("src"));
+        assert_eq!(embed.prefixes, vec![(
+            "assets/".to_string(),
+            PathBuf::from("out/assets")
+        )]);
+
+        let embed = Export::wechat("out", "").embed_images();
+        assert_eq!(embed.base_dir, PathBuf::from("out"));
+        assert!(embed.prefixes.is_empty());
+
+        let embed = Export::wechat("out", "https://x.com/{path}").embed_images();
+        assert!(embed.prefixes.is_empty());
+    }
+
+    #[test]
+    fn test_feature_from_str() {
+        assert_eq!("table".parse::<Feature>().unwrap(), Feature::Table);
+        assert!("foo".parse::<Feature>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_link_footnotes() {
+        let mut export = Export::new("assets", "assets");
+        export.footnotes = FootnoteStyle::LinkReferences;
+
+        let md = "See [the *docs*](https://a.com/x) and [again](https://a.com/x),\n[local](./b.md), <https://c.com>.\n";
+        let got = export.run(md).await.unwrap();
+
+        assert_eq!(
+            got,
+            "See the *docs*\\[1\\] and again\\[1\\],\n[local](./b.md), \\[2\\].\n\n---\n\n\\[1\\] `https://a.com/x`\n\n\\[2\\] `https://c.com`\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_end_notes() {
+        let mut export = Export::new("assets", "assets");
+        export.footnotes = FootnoteStyle::EndNotes;
+
+        let md = "# T\n\nA[^1] and [b](https://b.com).\n\n- x\n\n[^1]: The *note*.\n";
+        let got = export.run(md).await.unwrap();
+
+        assert_eq!(
+            got,
+            "# T\n\nA\\[1\\] and [b](https://b.com).\n\n- x\n\n---\n\n\\[1\\] The *note*.\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_assets() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("docs");
+        std::fs::create_dir_all(src.join("img")).unwrap();
+        std::fs::write(src.join("img/a.png"), b"\x89PNG\r\n\x1a\nA").unwrap();
+        std::fs::write(src.join("img/copy.png"), b"\x89PNG\r\n\x1a\nA").unwrap();
+        std::fs::write(src.join("b.gif"), b"GIF89a").unwrap();
+
+        let mut export = Export::new(dir.path().join("out/assets"), "assets");
+        export.source_dir = Some(src);
+
+        let md = "![a \"pic\"](img/a.png \"T\") ![same](./img/copy.png)\n\n![remote](https://a.com/x.png) [doc](b.md) ![r][logo] [link][doc]\n\n[logo]: b.gif\n[doc]: b.md\n";
+        let got = export.run(md).await.unwrap();
+
+        assert_eq!(
+            got,
+            "![a \"pic\"](assets/7133ad0828431796.png \"T\") ![same](assets/7133ad0828431796.png)\n\n![remote](https://a.com/x.png) [doc](b.md) ![r][logo] [link][doc]\n\n[logo]: assets/610f5ae4d76e3326.gif\n[doc]: b.md\n"
+        );
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("out/assets"))
+                .unwrap()
+                .count(),
+            2
+        );
+
+        let err = export.run("text\n\n![x](missing.png)\n").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to collect image missing.png at line 3"
+        );
+    }
+
+    #[test]
+    fn test_title_suffix() {
+        assert_eq!(title_suffix(None), "");
+        assert_eq!(title_suffix(Some(r#"a "b""#)), r#" "a \"b\"""#);
+    }
+
+    #[test]
+    fn test_is_complex_code() {
+        let mut export = Export::new("a", "a");
+        export.max_code_width = 4;
+
+        assert!(!export.is_complex_code("abcd\nab"));
+        assert!(export.is_complex_code("abcde"));
+        assert!(export.is_complex_code("│"));
+    }
+
+    #[test]
+    fn test_asset_link() {
+        assert_eq!(Export::new("a", "").asset_link("x.png"), "x.png");
+        assert_eq!(Export::new("a", "img/").asset_link("x.png"), "img/x.png");
+        assert_eq!(
+            Export::new("a", "https://a.com/{path}?raw=1").asset_link("x.png"),
+            "https://a.com/x.png?raw=1"
+        );
+    }
+
+    #[test]
+    fn test_escape_alt() {
+        assert_eq!(escape_alt("a[1]\\b\nc"), "a\\[1\\]\\\\b c");
+    }
+
+    #[tokio::test]
+    async fn test_run_dot() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut export = Export::new(dir.path(), "assets");
+        export.features.insert(Feature::Dot);
+        export.dot = Graphviz::Binary(PathBuf::from("/nonexistent/dot"));
+
+        let err = export
+            .run("# T\n\n```dot\ndigraph { a }\n```\n")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("dot not found: /nonexistent/dot;"));
+
+        export.dot = Graphviz::Builtin;
+        let err = export
+            .run("# T\n\n```dot\ndigraph { a -> }\n```\n")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid dot diagram at line 3");
+
+        let got = export.run("# T\n\ntext\n").await.unwrap();
+        assert_eq!(got, "# T\n\ntext\n");
+    }
+}
@@ -0,0 +1,211 @@
+//! Publish exported images to a git repository, so that the output links to them by url.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Context;
+
+/// Url of a published file on GitHub.
+pub const GITHUB_RAW_URL: &str = "https://raw.githubusercontent.com/{repo}/{branch}/{path}";
+
+/// Commits the files of an asset dir into a branch of a git repository and pushes it.
+///
+/// The `git` command is used, so the credentials configured for it apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitPublisher {
+    /// The repository to push to: a url such as `git@github.com:user/assets.git`, or a path.
+    pub repo: String,
+
+    pub branch: String,
+
+    /// The url of a published file, with the placeholders:
+    ///
+    /// * `{repo}` - the repository path without the host and ".git", such as "user/assets"
+    /// * `{branch}` - the branch
+    /// * `{path}` - the file path in the repository
+    pub url_template: String,
+}
+
+impl GitPublisher {
+    pub fn new(
+        repo: impl Into<String>,
+        branch: impl Into<String>,
+        url_template: impl Into<String>,
+    ) -> Self {
+        Self {
+            repo: repo.into(),
+            branch: branch.into(),
+            url_template: url_template.into(),
+        }
+    }
+
+    /// The url of a published file, with `{path}` left for [`Export::asset_url`].
+    ///
+    /// [`Export::asset_url`]: crate::export::Export::asset_url
+    pub fn asset_url(&self) -> String {
+        self.url_template
+            .replace("{repo}", &repo_path(&self.repo))
+            .replace("{branch}", &self.branch)
+    }
+
+    /// Commit the files in `asset_dir` to the top of the branch and push it, creating the
+    /// branch if it does not exist. Return the names of the files that were added.
+    pub fn publish(&self, asset_dir: &Path) -> anyhow::Result<Vec<String>> {
+        let work = tempfile::tempdir()?;
+        let work = work.path();
+
+        let heads = git(None, &["ls-remote", "--heads", &self.repo, &self.branch])?;
+
+        if heads.trim().is_empty() {
+            git(Some(work), &["init", "--quiet"])?;
+            git(Some(work), &["remote", "add", "origin", &self.repo])?;
+            git(Some(work), &[
+                "checkout",
+                "--quiet",
+                "--orphan",
+                &self.branch,
+            ])?;
+        } else {
+            let dest = work.to_string_lossy();
+            let args = [
+                "clone",
+                "--quiet",
+                "--depth",
+                "1",
+                "--branch",
+                &self.branch,
+                &self.repo,
+                &dest,
+            ];
+            git(None, &args)?;
+        }
+
+        for entry in fs::read_dir(asset_dir)
+            .with_context(|| format!("Failed to read asset dir: {}", asset_dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_file() {
+                fs::copy(&path, work.join(path.file_name().unwrap_or_default()))?;
+            }
+        }
+
+        git(Some(work), &["add", "--all"])?;
+        let added = git(Some(work), &["diff", "--cached", "--name-only"])?;
+        let added: Vec<String> = added.lines().map(|l| l.to_string()).collect();
+        if added.is_empty() {
+            return Ok(added);
+        }
+
+        let message = format!("Add {} assets", added.len());
+        let mut args = vec![];
+        if git(Some(work), &["config", "user.email"]).is_err() {
+            args.extend(["-c", "user.name=xpmd", "-c", "user.email=xpmd@localhost"]);
+        }
+        args.extend(["commit", "--quiet", "-m", &message]);
+        git(Some(work), &args)?;
+
+        let refspec = format!("HEAD:refs/heads/{}", self.branch);
+        git(Some(work), &["push", "--quiet", "origin", &refspec])
+            .with_context(|| format!("Failed to push assets to {}", self.repo))?;
+
+        Ok(added)
+    }
+}
+
+/// Run git in `dir` and return its stdout.
+fn git(dir: Option<&Path>, args: &[&str]) -> anyhow::Result<String> {
+    let mut cmd = Command::new("git");
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+
+    let output = cmd
+        .args(args)
+        .output()
+        .context("Failed to run git, is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The repository path without the host and ".git", such as "user/assets" for
+/// `git@github.com:user/assets.git`. For a local path it is the directory name.
+fn repo_path(repo: &str) -> String {
+    let repo = repo.trim_end_matches('/');
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+
+    let path = if let Some((_, rest)) = repo.split_once("://") {
+        rest.split_once('/')
+            .map(|(_, path)| path)
+            .unwrap_or_default()
+    } else if let Some((_, path)) = repo.split_once(':').filter(|(host, _)| host.contains('@')) {
+        path
+    } else {
+        repo.rsplit('/').next().unwrap_or(repo)
+    };
+
+    path.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_path() {
+        assert_eq!(
+            repo_path("https://github.com/user/assets.git"),
+            "user/assets"
+        );
+        assert_eq!(repo_path("https://github.com/user/assets/"), "user/assets");
+        assert_eq!(repo_path("git@github.com:user/assets.git"), "user/assets");
+        assert_eq!(repo_path("/tmp/x/assets.git"), "assets");
+    }
+
+    #[test]
+    fn test_asset_url() {
+        let p = GitPublisher::new("git@github.com:user/assets.git", "main", GITHUB_RAW_URL);
+        assert_eq!(
+            p.asset_url(),
+            "https://raw.githubusercontent.com/user/assets/main/{path}"
+        );
+    }
+
+    #[test]
+    fn test_publish_to_bare_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("remote.git");
+        git(None, &[
+            "init",
+            "--quiet",
+            "--bare",
+            &repo.to_string_lossy(),
+        ])
+        .unwrap();
+
+        let assets = dir.path().join("assets");
+        fs::create_dir_all(&assets).unwrap();
+        fs::write(assets.join("a.png"), "a").unwrap();
+
+        let p = GitPublisher::new(repo.to_string_lossy(), "img", GITHUB_RAW_URL);
+
+        // The branch is created on the first push.
+        assert_eq!(p.publish(&assets).unwrap(), vec!["a.png"]);
+
+        // Files already published are not committed again.
+        fs::write(assets.join("b.png"), "b").unwrap();
+        assert_eq!(p.publish(&assets).unwrap(), vec!["b.png"]);
+        assert!(p.publish(&assets).unwrap().is_empty());
+
+        let files = git(Some(&repo), &["ls-tree", "--name-only", "img"]).unwrap();
+        assert_eq!(files, "a.png\nb.png\n");
+    }
+}
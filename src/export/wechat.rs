@@ -0,0 +1,167 @@
+//! Export for WeChat Official Account articles.
+//!
+//! The WeChat editor strips `<style>` tags and classes from pasted html, and blocks external
+//! links. The export produces html with the GitHub markdown styles inlined into every element,
+//! embeds local images as `data:` urls, and lists external links at the end of the article.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::export::Export;
+use crate::export::Feature;
+use crate::export::Output;
+use crate::md::transform::FootnoteStyle;
+use crate::md::unescape_html;
+use crate::render::dom_body;
+use crate::render::github_markdown_page;
+use crate::render::with_chrome::WithChrome;
+
+/// Prefix of the page body when the styles are inlined.
+const HTML_MARK: &str = "XPMD-INLINE-CSS:";
+
+impl Export {
+    /// Export settings for WeChat.
+    ///
+    /// Tables and code blocks are kept as html; add [`Feature::Table`] or [`Feature::Code`] to
+    /// `features` to render them to images.
+    pub fn wechat(asset_dir: impl Into<PathBuf>, asset_url: impl Into<String>) -> Self {
+        Self {
+            features: BTreeSet::from([Feature::Math, Feature::Mermaid, Feature::Dot]),
+            footnotes: FootnoteStyle::LinkReferences,
+            output: Output::InlineStyledHtml,
+            ..Export::new(asset_dir, asset_url)
+        }
+    }
+}
+
+/// Style html converted from markdown with the GitHub markdown stylesheet, and inline the
+/// computed styles of every element as `style=""` attributes.
+///
+/// Classes and ids are removed, and the content is wrapped in a `<section>`, which is what the
+/// WeChat editor keeps.
+pub async fn inline_css(html: &str) -> anyhow::Result<String> {
+    let page = build_page(html);
+    let dom = WithChrome::dump_dom("text/html", &page, None, None).await?;
+
+    parse_dom(&dom)
+}
+
+/// Build a page that computes the styles and replaces the body with the result as text.
+fn build_page(html: &str) -> String {
+    let script = format!(
+        r#"<script>
+(() => {{
+  // Properties inherited from the parent element.
+  const inherited = [
+    "color", "font-family", "font-size", "font-style", "font-weight", "line-height",
+    "letter-spacing", "text-align", "white-space", "word-break", "list-style-type",
+  ];
+  const others = [
+    "display", "background-color", "text-decoration-line",
+    "margin-top", "margin-right", "margin-bottom", "margin-left",
+    "padding-top", "padding-right", "padding-bottom", "padding-left",
+    "border-top", "border-right", "border-bottom", "border-left", "border-radius",
+    "border-collapse", "border-spacing", "width", "max-width", "overflow-x", "vertical-align",
+  ];
+
+  // Default styles of every tag, from an element in an unstyled document.
+  const frame = document.createElement("iframe");
+  document.body.appendChild(frame);
+  const defaults = {{}};
+  const defaultStyle = (tag) => {{
+    if (!defaults[tag]) {{
+      const el = frame.contentDocument.createElement(tag);
+      frame.contentDocument.body.appendChild(el);
+      const cs = getComputedStyle(el);
+      defaults[tag] = Object.fromEntries(others.map((p) => [p, cs.getPropertyValue(p)]));
+      el.remove();
+    }}
+    return defaults[tag];
+  }};
+
+  const root = document.querySelector(".markdown-body");
+  const elements = [root, ...root.querySelectorAll("*")];
+
+  // Compute all styles before changing anything, since removing classes changes them.
+  const styles = elements.map((el) => {{
+    const cs = getComputedStyle(el);
+    const parent = el === root ? null : getComputedStyle(el.parentElement);
+    const dflt = defaultStyle(el.tagName.toLowerCase());
+    const decls = [];
+    for (const p of inherited) {{
+      const v = cs.getPropertyValue(p);
+      if (!parent || parent.getPropertyValue(p) !== v) decls.push(p + ": " + v);
+    }}
+    for (const p of others) {{
+      const v = cs.getPropertyValue(p);
+      if (dflt[p] !== v) decls.push(p + ": " + v);
+    }}
+    return decls.join("; ");
+  }});
+
+  elements.forEach((el, i) => {{
+    el.removeAttribute("class");
+    el.removeAttribute("id");
+    if (styles[i]) el.setAttribute("style", styles[i]);
+  }});
+
+  const section = document.createElement("section");
+  section.setAttribute("style", root.getAttribute("style") || "");
+  section.append(...root.childNodes);
+
+  document.body.textContent = "{mark}" + section.outerHTML;
+}})();
+</script>"#,
+        mark = HTML_MARK
+    );
+
+    github_markdown_page(html).replace("</body>", &format!("{}\n</body>", script))
+}
+
+/// Extract the inlined html from the DOM dumped by chrome.
+fn parse_dom(dom: &str) -> anyhow::Result<String> {
+    match dom_body(dom).strip_prefix(HTML_MARK) {
+        Some(html) => Ok(unescape_html(html)),
+        None => anyhow::bail!(
+            "Failed to inline css, chrome output: {}",
+            dom.chars().take(200).collect::<String>()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wechat() {
+        let export = Export::wechat("assets", "assets");
+
+        assert_eq!(export.footnotes, FootnoteStyle::LinkReferences);
+        assert_eq!(export.output, Output::InlineStyledHtml);
+        assert!(!export.features.contains(&Feature::Table));
+    }
+
+    #[test]
+    fn test_build_page() {
+        let page = build_page("<p>hi</p>");
+
+        assert!(page.contains(r#"<article class="markdown-body"><p>hi</p></article>"#));
+        assert!(page.contains("</script>\n</body>"));
+        assert!(page.contains(HTML_MARK));
+    }
+
+    #[test]
+    fn test_parse_dom() {
+        let dom = format!(
+            "<html><head></head><body>{}&lt;section style=\"color: red\"&gt;&lt;p&gt;a &amp;amp; b&lt;/p&gt;&lt;/section&gt;</body></html>",
+            HTML_MARK
+        );
+        assert_eq!(
+            parse_dom(&dom).unwrap(),
+            r#"<section style="color: red"><p>a &amp; b</p></section>"#
+        );
+
+        assert!(parse_dom("<html><body></body></html>").is_err());
+    }
+}
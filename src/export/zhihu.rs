@@ -0,0 +1,29 @@
+//! Export for [Zhihu](https://www.zhihu.com) articles.
+//!
+//! The Zhihu editor imports markdown, but does not support formulas written in TeX, tables,
+//! diagrams, wide code blocks, or alerts, which are turned into images.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::export::Export;
+use crate::export::Feature;
+use crate::md::transform::FootnoteStyle;
+
+impl Export {
+    /// Export settings for Zhihu.
+    pub fn zhihu(asset_dir: impl Into<PathBuf>, asset_url: impl Into<String>) -> Self {
+        Self {
+            features: BTreeSet::from([
+                Feature::Math,
+                Feature::Table,
+                Feature::Mermaid,
+                Feature::Dot,
+                Feature::Code,
+                Feature::Alert,
+            ]),
+            footnotes: FootnoteStyle::EndNotes,
+            ..Export::new(asset_dir, asset_url)
+        }
+    }
+}
@@ -1,4 +1,14 @@
+pub mod book;
+pub mod check;
+pub mod config;
+pub mod doctor;
+pub mod export;
+pub mod md;
 pub(crate) mod mime;
+pub mod preview;
 pub mod render;
+pub mod server;
 
 pub use mime::Mime;
+pub use mime::MimeType;
+pub use mime::PathMime;
@@ -1,15 +1,20 @@
 use std::io::Read;
 use std::io::{self};
 
-fn main() -> anyhow::Result<()> {
+use xp_md2html::md::MarkdownToHtml;
+use xp_md2html::md::MathOutput;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     let mut md = String::new();
     io::stdin().read_to_string(&mut md)?;
 
-    println!(
-        "{}",
-        markdown::to_html_with_options(&md, &markdown::Options::gfm())
-            .map_err(|e| anyhow::anyhow!("{}", e.to_string()))?
-    );
+    let html = MarkdownToHtml::new()
+        .with_math(MathOutput::MathMl)
+        .render(&md)
+        .await?;
+
+    println!("{}", html);
 
     Ok(())
 }
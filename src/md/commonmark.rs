@@ -0,0 +1,845 @@
+//! Serialize a mdast tree to markdown (CommonMark with the GFM and math extensions).
+
+use std::collections::HashMap;
+
+use markdown::mdast::AlignKind;
+use markdown::mdast::Node;
+use markdown::mdast::ReferenceKind;
+use markdown::mdast::Table;
+
+use crate::md::parse;
+use crate::md::walk;
+
+/// Serialize a mdast tree to markdown.
+pub fn to_markdown(root: &Node) -> String {
+    MarkdownWriter::new(None, None).document(root)
+}
+
+/// Serialize a tree parsed from `source` and possibly changed since, such as by a
+/// [`Transform`].
+///
+/// Nodes that are not changed are written as they are in `source`, and the others follow the
+/// style of `source`: list markers, code fences, emphasis markers and heading styles. Thus a
+/// document round-trips exactly, and a change only touches the lines it is on.
+///
+/// [`Transform`]: crate::md::transform::Transform
+pub fn to_markdown_preserving(root: &Node, source: &str) -> anyhow::Result<String> {
+    let original = parse(source)?;
+
+    let mut by_range: HashMap<(usize, usize), Vec<&Node>> = HashMap::new();
+    walk(&original, &mut |node| {
+        if let Some(p) = node.position() {
+            by_range
+                .entry((p.start.offset, p.end.offset))
+                .or_default()
+                .push(node);
+        }
+    });
+
+    Ok(MarkdownWriter::new(Some(source), Some(by_range)).document(root))
+}
+
+/// How the content of a container is prefixed on every line, such as `> ` in a block quote.
+#[derive(Debug, Clone, Copy)]
+enum Prefix {
+    Quote,
+    Indent(usize),
+}
+
+struct MarkdownWriter<'a> {
+    source: Option<&'a str>,
+
+    /// Nodes of the tree parsed from `source` by their byte range. A node equal to the one at
+    /// its range is not changed, and is written as in the source. `None` to never reuse the
+    /// source as is, but still follow its style.
+    original: Option<HashMap<(usize, usize), Vec<&'a Node>>>,
+
+    /// Prefixes of the containers being written, outermost first.
+    prefixes: Vec<Prefix>,
+
+    /// Whether text is in a table cell, where `|` has to be escaped.
+    in_table: bool,
+
+    /// Style of the next list, chosen by its parent, see [`Self::list_style`].
+    list_style: Option<String>,
+}
+
+impl<'a> MarkdownWriter<'a> {
+    fn new(
+        source: Option<&'a str>,
+        original: Option<HashMap<(usize, usize), Vec<&'a Node>>>,
+    ) -> Self {
+        Self {
+            source,
+            original,
+            prefixes: vec![],
+            in_table: false,
+            list_style: None,
+        }
+    }
+
+    fn document(&mut self, root: &Node) -> String {
+        if let (Some(source), true) = (self.source, self.is_unchanged(root)) {
+            return source.to_string();
+        }
+
+        let children = root.children().map(|c| c.as_slice()).unwrap_or_default();
+        let mut out = self.blocks(children, "\n\n");
+
+        // Keep the line endings after the last block.
+        let tail = match (self.source, children.last().and_then(|n| self.end(n))) {
+            (Some(source), Some(end)) => source
+                .get(end..)
+                .filter(|tail| tail.trim().is_empty())
+                .map(|tail| "\n".repeat(tail.matches('\n').count())),
+            _ => None,
+        };
+        out.push_str(&tail.unwrap_or_else(|| "\n".to_string()));
+        out
+    }
+
+    /// Whether a node is the same as the one at its position in the source.
+    fn is_unchanged(&self, node: &Node) -> bool {
+        let (Some(original), Some(p)) = (&self.original, node.position()) else {
+            return false;
+        };
+        original
+            .get(&(p.start.offset, p.end.offset))
+            .is_some_and(|nodes| nodes.iter().any(|n| *n == node))
+    }
+
+    /// The source of a node, without the prefixes of the containers on every line but the
+    /// first.
+    fn slice(&self, node: &Node) -> Option<String> {
+        let p = node.position()?;
+        let text = self.source?.get(p.start.offset..self.end(node)?)?;
+
+        let mut lines = text.split('\n');
+        let mut out = lines.next().unwrap_or_default().to_string();
+        for line in lines {
+            out.push('\n');
+            out.push_str(strip_prefixes(line, &self.prefixes));
+        }
+        Some(out)
+    }
+
+    /// The first line of the source of a node, to infer the style of a changed node.
+    fn first_line(&self, node: &Node) -> Option<String> {
+        let slice = self.slice(node)?;
+        Some(slice.split('\n').next().unwrap_or_default().to_string())
+    }
+
+    /// Write blocks, separated as in the source, or with `separator` for new nodes.
+    fn blocks(&mut self, children: &[Node], separator: &str) -> String {
+        let mut out = String::new();
+
+        let mut prev_list_style = None;
+
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 {
+                out.push_str(&self.separator(&children[i - 1], child, separator));
+            }
+
+            // Adjacent lists need different markers, or they are one list.
+            if let Node::List(_) = child {
+                let mut style = self.list_style(child);
+                if prev_list_style.as_ref() == Some(&style) {
+                    style = alternate_list_style(&style).to_string();
+                }
+                self.list_style = Some(style.clone());
+                prev_list_style = Some(style);
+            } else {
+                prev_list_style = None;
+            }
+
+            out.push_str(&self.block(child));
+        }
+
+        out
+    }
+
+    /// The line breaks between two sibling blocks.
+    fn separator(&self, prev: &Node, next: &Node, default: &str) -> String {
+        let (Some(source), Some(end), Some(b)) = (self.source, self.end(prev), next.position())
+        else {
+            return default.to_string();
+        };
+
+        match source.get(end..b.start.offset) {
+            Some(gap) if gap.contains('\n') => "\n".repeat(gap.matches('\n').count()),
+            _ => default.to_string(),
+        }
+    }
+
+    /// The end offset of a node in the source, before trailing line endings, which a list
+    /// includes. A text keeps them, since they are part of its value.
+    fn end(&self, node: &Node) -> Option<usize> {
+        let p = node.position()?;
+        if let Node::Text(_) = node {
+            return Some(p.end.offset);
+        }
+        let text = self.source?.get(p.start.offset..p.end.offset)?;
+        Some(p.start.offset + text.trim_end_matches(['\n', '\r']).len())
+    }
+
+    fn block(&mut self, node: &Node) -> String {
+        if self.is_unchanged(node) {
+            if let Some(slice) = self.slice(node) {
+                return slice;
+            }
+        }
+
+        match node {
+            Node::Paragraph(_) => self.inlines(node),
+            Node::Heading(h) => {
+                let content = self.inlines(node);
+                let first = self.first_line(node).unwrap_or_default();
+
+                if h.depth <= 2 && !first.trim_start().starts_with('#') && !first.is_empty() {
+                    // Setext heading: keep the underline of the source.
+                    let slice = self.slice(node).unwrap_or_default();
+                    let underline = slice.lines().last().unwrap_or_default().trim();
+                    format!("{}\n{}", content, underline)
+                } else {
+                    let hashes = "#".repeat(h.depth as usize);
+                    // Keep the closing sequence, such as in `## Title ##`.
+                    let closing = first
+                        .trim_end()
+                        .rsplit_once(' ')
+                        .map(|(_, last)| last)
+                        .filter(|last| !last.is_empty() && last.chars().all(|c| c == '#'))
+                        .map(|last| format!(" {}", last))
+                        .unwrap_or_default();
+                    if content.is_empty() {
+                        hashes
+                    } else {
+                        format!("{} {}{}", hashes, content, closing)
+                    }
+                }
+            }
+            Node::ThematicBreak(_) => self
+                .slice(node)
+                .map(|s| s.trim().to_string())
+                .unwrap_or_else(|| "---".to_string()),
+            Node::Blockquote(b) => {
+                self.prefixes.push(Prefix::Quote);
+                let content = self.blocks(&b.children, "\n\n");
+                self.prefixes.pop();
+                prefix_lines(&content, "> ", "> ")
+            }
+            Node::List(list) => {
+                let loose = list.spread
+                    || list
+                        .children
+                        .iter()
+                        .any(|item| matches!(item, Node::ListItem(item) if item.spread));
+                let separator = if loose { "\n\n" } else { "\n" };
+
+                let style = self
+                    .list_style
+                    .take()
+                    .unwrap_or_else(|| self.list_style(node));
+                let mut out = String::new();
+                for (i, item) in list.children.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(&self.separator(&list.children[i - 1], item, separator));
+                    }
+                    let marker =
+                        self.item_marker(item, &style, list.start.unwrap_or(1) as usize + i);
+                    out.push_str(&self.list_item(item, &marker));
+                }
+                out
+            }
+            Node::ListItem(_) => self.list_item(node, "-"),
+            Node::Code(code) => self.code(
+                node,
+                &code.value,
+                code.lang.as_deref(),
+                code.meta.as_deref(),
+            ),
+            Node::Math(math) => {
+                let fence = self
+                    .first_line(node)
+                    .map(|line| leading_run(line.trim_start(), '$'))
+                    .filter(|n| *n >= 2)
+                    .unwrap_or(2);
+                let fence = "$".repeat(fence);
+                let meta = math
+                    .meta
+                    .as_deref()
+                    .map(|m| format!(" {}", m))
+                    .unwrap_or_default();
+                if math.value.is_empty() {
+                    format!("{}{}\n{}", fence, meta, fence)
+                } else {
+                    format!("{}{}\n{}\n{}", fence, meta, math.value, fence)
+                }
+            }
+            Node::Html(html) => html.value.clone(),
+            Node::Definition(def) => format!(
+                "[{}]: {}{}",
+                def.label.as_deref().unwrap_or(&def.identifier),
+                destination(&def.url, true),
+                title(def.title.as_deref())
+            ),
+            Node::FootnoteDefinition(def) => {
+                self.prefixes.push(Prefix::Indent(4));
+                let content = self.blocks(&def.children, "\n\n");
+                self.prefixes.pop();
+                let first = format!("[^{}]: ", def.label.as_deref().unwrap_or(&def.identifier));
+                prefix_lines(&content, &first, "    ")
+            }
+            Node::Table(table) => self.table(table),
+            Node::Yaml(yaml) => format!("---\n{}\n---", yaml.value),
+            Node::Toml(toml) => format!("+++\n{}\n+++", toml.value),
+            _ => self.inline(node, true),
+        }
+    }
+
+    /// The bullet, such as `-`, or the delimiter after the number, such as `.`, of a list.
+    fn list_style(&self, list: &Node) -> String {
+        let ordered = matches!(list, Node::List(l) if l.ordered);
+        let items = list.children().map(|c| c.as_slice()).unwrap_or_default();
+
+        let from_source = items.iter().find_map(|item| {
+            let line = self.first_line(item)?;
+            let marker = line.split_whitespace().next()?.to_string();
+            if ordered {
+                marker.chars().last().map(|c| c.to_string())
+            } else {
+                marker.chars().next().map(|c| c.to_string())
+            }
+        });
+
+        from_source.unwrap_or_else(|| if ordered { "." } else { "-" }.to_string())
+    }
+
+    fn item_marker(&self, item: &Node, style: &str, number: usize) -> String {
+        if let Some(marker) = self
+            .first_line(item)
+            .and_then(|line| line.split_whitespace().next().map(|m| m.to_string()))
+        {
+            return marker;
+        }
+
+        if style == "." || style == ")" {
+            format!("{}{}", number, style)
+        } else {
+            style.to_string()
+        }
+    }
+
+    fn list_item(&mut self, node: &Node, marker: &str) -> String {
+        let Node::ListItem(item) = node else {
+            return self.block(node);
+        };
+        if self.is_unchanged(node) {
+            if let Some(slice) = self.slice(node) {
+                return slice;
+            }
+        }
+
+        // The content is indented as in the source: the marker and the spaces after it.
+        let indent = self
+            .first_line(node)
+            .map(|line| {
+                let after = line.trim_start()[marker.len()..].to_string();
+                let spaces = leading_run(&after, ' ');
+                if after.trim().is_empty() || spaces > 4 {
+                    marker.len() + 1
+                } else {
+                    marker.len() + spaces
+                }
+            })
+            .unwrap_or(marker.chars().count() + 1);
+
+        let separator = if item.spread { "\n\n" } else { "\n" };
+        self.prefixes.push(Prefix::Indent(indent));
+        let mut content = self.blocks(&item.children, separator);
+        self.prefixes.pop();
+
+        if let Some(checked) = item.checked {
+            let checkbox = if checked { "[x]" } else { "[ ]" };
+            content = format!("{} {}", checkbox, content);
+        }
+
+        if content.is_empty() {
+            return marker.to_string();
+        }
+
+        let first = format!("{:<width$}", marker, width = indent);
+        prefix_lines(&content, &first, &" ".repeat(indent))
+    }
+
+    fn code(&self, node: &Node, value: &str, lang: Option<&str>, meta: Option<&str>) -> String {
+        let first = self.first_line(node);
+        let trimmed = first.as_deref().map(|l| l.trim_start()).unwrap_or_default();
+
+        // An indented code block in the source stays indented.
+        if first.is_some() && !trimmed.starts_with("```") && !trimmed.starts_with("~~~") {
+            return value
+                .split('\n')
+                .map(|line| {
+                    if line.is_empty() {
+                        String::new()
+                    } else {
+                        format!("    {}", line)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+
+        let fence_char = if trimmed.starts_with('~') { '~' } else { '`' };
+        let longest_inside = value
+            .lines()
+            .map(|line| leading_run(line.trim_start(), fence_char))
+            .max()
+            .unwrap_or_default();
+        let len = leading_run(trimmed, fence_char)
+            .max(3)
+            .max(longest_inside + 1);
+        let fence = fence_char.to_string().repeat(len);
+
+        let mut info = lang.unwrap_or_default().to_string();
+        if let Some(meta) = meta {
+            info.push(' ');
+            info.push_str(meta);
+        }
+
+        if value.is_empty() {
+            format!("{}{}\n{}", fence, info, fence)
+        } else {
+            format!("{}{}\n{}\n{}", fence, info, value, fence)
+        }
+    }
+
+    fn table(&mut self, table: &Table) -> String {
+        let in_table = std::mem::replace(&mut self.in_table, true);
+        let rows: Vec<Vec<String>> = table
+            .children
+            .iter()
+            .map(|row| {
+                let cells = row.children().map(|c| c.as_slice()).unwrap_or_default();
+                cells.iter().map(|cell| self.inlines(cell)).collect()
+            })
+            .collect();
+        self.in_table = in_table;
+
+        let columns = table
+            .align
+            .len()
+            .max(rows.iter().map(|r| r.len()).max().unwrap_or_default());
+        let widths: Vec<usize> = (0..columns)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|r| r.get(i))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or_default()
+                    .max(3)
+            })
+            .collect();
+
+        let row_line = |cells: &[String]| {
+            let cells: Vec<String> = (0..columns)
+                .map(|i| {
+                    let cell = cells.get(i).map(|c| c.as_str()).unwrap_or_default();
+                    let pad = widths[i] - cell.chars().count();
+                    format!(" {}{} ", cell, " ".repeat(pad))
+                })
+                .collect();
+            format!("|{}|", cells.join("|"))
+        };
+
+        let delimiter: Vec<String> = (0..columns)
+            .map(|i| {
+                let w = widths[i];
+                let cell = match table.align.get(i) {
+                    Some(AlignKind::Left) => format!(":{}", "-".repeat(w - 1)),
+                    Some(AlignKind::Right) => format!("{}:", "-".repeat(w - 1)),
+                    Some(AlignKind::Center) => format!(":{}:", "-".repeat(w - 2)),
+                    _ => "-".repeat(w),
+                };
+                format!(" {} ", cell)
+            })
+            .collect();
+
+        let mut lines = vec![];
+        for (i, row) in rows.iter().enumerate() {
+            lines.push(row_line(row));
+            if i == 0 {
+                lines.push(format!("|{}|", delimiter.join("|")));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Write the children of a node as inline content.
+    fn inlines(&mut self, node: &Node) -> String {
+        let children = node.children().map(|c| c.as_slice()).unwrap_or_default();
+        let mut out = String::new();
+        for (i, child) in children.iter().enumerate() {
+            out.push_str(&self.inline(child, i == 0));
+        }
+        out
+    }
+
+    /// Write an inline node; `line_start` is whether it starts a line of the block.
+    fn inline(&mut self, node: &Node, line_start: bool) -> String {
+        if self.is_unchanged(node) && !matches!(node, Node::Break(_)) {
+            if let Some(slice) = self.slice(node) {
+                return slice;
+            }
+        }
+
+        match node {
+            Node::Text(text) => {
+                // Text written without escapes in the source is safe to write as is.
+                match self.slice(node) {
+                    Some(slice) if slice == text.value => slice,
+                    _ => escape_text(&text.value, line_start, self.in_table),
+                }
+            }
+            Node::Emphasis(_) => {
+                let marker = self
+                    .first_line(node)
+                    .and_then(|l| l.chars().next())
+                    .filter(|c| *c == '_')
+                    .unwrap_or('*');
+                format!("{}{}{}", marker, self.inlines(node), marker)
+            }
+            Node::Strong(_) => {
+                let marker = match self.first_line(node) {
+                    Some(l) if l.starts_with("__") => "__",
+                    _ => "**",
+                };
+                format!("{}{}{}", marker, self.inlines(node), marker)
+            }
+            Node::Delete(_) => {
+                let marker = match self.first_line(node) {
+                    Some(l) if !l.starts_with("~~") => "~",
+                    _ => "~~",
+                };
+                format!("{}{}{}", marker, self.inlines(node), marker)
+            }
+            Node::InlineCode(code) if self.in_table => {
+                code_span(&code.value, '`').replace('|', "\\|")
+            }
+            Node::InlineCode(code) => code_span(&code.value, '`'),
+            Node::InlineMath(math) => code_span(&math.value, '$'),
+            Node::Break(_) => match self.slice(node) {
+                Some(spaces) if spaces.starts_with(' ') => format!("{}\n", spaces),
+                _ => "\\\n".to_string(),
+            },
+            Node::Html(html) => html.value.clone(),
+            Node::Link(link) => {
+                let text = self.inlines(node);
+                let first = self.first_line(node).unwrap_or_default();
+                let plain = node.to_string();
+
+                if link.title.is_none() {
+                    let literal = !first.is_empty() && !first.starts_with(['[', '<']);
+
+                    if plain.starts_with("www.") && link.url == format!("http://{}", plain) {
+                        return plain;
+                    }
+                    if plain == link.url || format!("mailto:{}", plain) == link.url {
+                        // A GFM autolink literal stays one, the others become `<url>`, since
+                        // a literal in link text would be a link in a link.
+                        return if literal {
+                            plain
+                        } else {
+                            format!("<{}>", plain)
+                        };
+                    }
+                }
+
+                format!(
+                    "[{}]({}{})",
+                    text,
+                    destination(&link.url, false),
+                    title(link.title.as_deref())
+                )
+            }
+            Node::Image(image) => format!(
+                "![{}]({}{})",
+                escape_alt(&image.alt),
+                destination(&image.url, false),
+                title(image.title.as_deref())
+            ),
+            Node::LinkReference(r) => {
+                let text = self.inlines(node);
+                let label = r.label.as_deref().unwrap_or(&r.identifier);
+                match r.reference_kind {
+                    ReferenceKind::Full => format!("[{}][{}]", text, label),
+                    ReferenceKind::Collapsed => format!("[{}][]", text),
+                    ReferenceKind::Shortcut => format!("[{}]", text),
+                }
+            }
+            Node::ImageReference(r) => {
+                let alt = escape_alt(&r.alt);
+                let label = r.label.as_deref().unwrap_or(&r.identifier);
+                match r.reference_kind {
+                    ReferenceKind::Full => format!("![{}][{}]", alt, label),
+                    ReferenceKind::Collapsed => format!("![{}][]", alt),
+                    ReferenceKind::Shortcut => format!("![{}]", alt),
+                }
+            }
+            Node::FootnoteReference(r) => {
+                format!("[^{}]", r.label.as_deref().unwrap_or(&r.identifier))
+            }
+            _ => self.inlines(node),
+        }
+    }
+}
+
+fn alternate_list_style(style: &str) -> &'static str {
+    match style {
+        "-" => "*",
+        "." => ")",
+        ")" => ".",
+        _ => "-",
+    }
+}
+
+/// Remove the prefixes of containers from a line of source.
+fn strip_prefixes<'s>(line: &'s str, prefixes: &[Prefix]) -> &'s str {
+    let mut rest = line;
+
+    for prefix in prefixes {
+        match prefix {
+            Prefix::Quote => {
+                let trimmed = rest.trim_start_matches(' ');
+                match trimmed.strip_prefix('>') {
+                    Some(r) => rest = r.strip_prefix(' ').unwrap_or(r),
+                    // A lazy continuation line has no `>`.
+                    None => return rest,
+                }
+            }
+            Prefix::Indent(n) => {
+                let spaces = rest.bytes().take(*n).take_while(|b| *b == b' ').count();
+                rest = &rest[spaces..];
+            }
+        }
+    }
+
+    rest
+}
+
+/// Prefix the first line with `first` and the others with `rest`; empty lines get `rest`
+/// without trailing spaces.
+fn prefix_lines(content: &str, first: &str, rest: &str) -> String {
+    content
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn leading_run(s: &str, c: char) -> usize {
+    s.chars().take_while(|x| *x == c).count()
+}
+
+/// Write code or math as a span delimited by `delimiter`, long enough not to appear inside.
+fn code_span(value: &str, delimiter: char) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in value.chars() {
+        if c == delimiter {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    let fence = delimiter.to_string().repeat(longest + 1);
+
+    let needs_padding = value.starts_with(delimiter)
+        || value.ends_with(delimiter)
+        || (value.starts_with(' ') && value.ends_with(' ') && !value.trim().is_empty());
+    if needs_padding {
+        format!("{} {} {}", fence, value, fence)
+    } else {
+        format!("{}{}{}", fence, value, fence)
+    }
+}
+
+/// Write a link destination, in `<>` if it has spaces or unbalanced parentheses.
+fn destination(url: &str, definition: bool) -> String {
+    let balanced = url.matches('(').count() == url.matches(')').count();
+    if url.is_empty() && definition || url.contains([' ', '<', '>']) || !balanced {
+        format!("<{}>", url.replace('<', "\\<").replace('>', "\\>"))
+    } else {
+        url.to_string()
+    }
+}
+
+fn title(title: Option<&str>) -> String {
+    match title {
+        Some(title) => format!(" \"{}\"", title.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => String::new(),
+    }
+}
+
+fn escape_alt(alt: &str) -> String {
+    alt.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+/// Escape characters in text that markdown would otherwise parse as syntax.
+fn escape_text(text: &str, line_start: bool, in_table: bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut at_line_start = line_start;
+
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|j| chars[j]);
+        let next = chars.get(i + 1).copied();
+
+        let escape = match c {
+            '\\' | '`' | '*' | '[' | ']' | '<' | '$' | '~' => true,
+            '|' => in_table,
+            '_' => {
+                !(prev.is_some_and(char::is_alphanumeric)
+                    && next.is_some_and(char::is_alphanumeric))
+            }
+            '&' => next.is_some_and(|n| n.is_alphanumeric() || n == '#'),
+            '#' | '>' | '=' => at_line_start,
+            '-' | '+' => at_line_start && matches!(next, None | Some(' ')),
+            '.' | ')' => {
+                // `1.` at the start of a line is a list item.
+                let digits = chars[..i]
+                    .iter()
+                    .rev()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                digits > 0
+                    && (i == digits && line_start || i > digits && chars[i - digits - 1] == '\n')
+                    && matches!(next, None | Some(' '))
+            }
+            _ => false,
+        };
+
+        if escape {
+            out.push('\\');
+        }
+        out.push(c);
+
+        at_line_start = c == '\n' || (at_line_start && c == ' ');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::md::walk_mut;
+
+    fn fixtures() -> Vec<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/markdown");
+        let mut fixtures: Vec<(String, String)> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, fs::read_to_string(&path).unwrap())
+            })
+            .collect();
+        fixtures.sort();
+        fixtures
+    }
+
+    fn without_positions(mut root: Node) -> Node {
+        walk_mut(&mut root, &mut |node| node.position_set(None));
+        root
+    }
+
+    /// Every node is written from the tree, following the style of the source.
+    #[test]
+    fn test_fixtures_round_trip() {
+        for (name, md) in fixtures() {
+            let root = parse(&md).unwrap();
+            let got = MarkdownWriter::new(Some(&md), None).document(&root);
+            assert_eq!(got, md, "fixture: {}", name);
+        }
+    }
+
+    /// Without a source the style differs, but the markdown parses to the same tree.
+    #[test]
+    fn test_fixtures_same_tree() {
+        for (name, md) in fixtures() {
+            let root = without_positions(parse(&md).unwrap());
+            let written = to_markdown(&root);
+            let reparsed = without_positions(parse(&written).unwrap());
+            assert_eq!(reparsed, root, "fixture: {}\n{}", name, written);
+        }
+    }
+
+    #[test]
+    fn test_preserving_changes_only_changed_lines() {
+        let md = "# Title\n\n* a  [x](x.md)\n* b\n\n\n| a |\n|---|\n";
+        let mut root = parse(md).unwrap();
+
+        assert_eq!(to_markdown_preserving(&root, md).unwrap(), md);
+
+        walk_mut(&mut root, &mut |node| {
+            if let Node::Link(link) = node {
+                link.url = "x.html".to_string();
+            }
+        });
+        assert_eq!(
+            to_markdown_preserving(&root, md).unwrap(),
+            "# Title\n\n* a  [x](x.html)\n* b\n\n\n| a |\n|---|\n"
+        );
+    }
+
+    #[test]
+    fn test_new_nodes() {
+        let mut root = parse("- a\n").unwrap();
+        let list = root.children_mut().unwrap().pop().unwrap();
+        root.children_mut().unwrap().extend([
+            Node::Html(markdown::mdast::Html {
+                value: "<hr>".to_string(),
+                position: None,
+            }),
+            list,
+        ]);
+
+        assert_eq!(to_markdown(&root), "<hr>\n\n- a\n");
+    }
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(
+            escape_text("a*b_c [x] $1 <b>", false, false),
+            "a\\*b_c \\[x\\] \\$1 \\<b>"
+        );
+        assert_eq!(
+            escape_text("# h\n- x\n1. y", true, false),
+            "\\# h\n\\- x\n1\\. y"
+        );
+        assert_eq!(escape_text("_a_ |", false, true), "\\_a\\_ \\|");
+        assert_eq!(escape_text("&amp; & 2", false, false), "\\&amp; & 2");
+    }
+
+    #[test]
+    fn test_code_span() {
+        assert_eq!(code_span("a", '`'), "`a`");
+        assert_eq!(code_span("a ` b", '`'), "``a ` b``");
+        assert_eq!(code_span("`a", '`'), "`` `a ``");
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::ErrorKind;
+use std::io::Write;
+use std::panic;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use layout::backends::svg::SVGWriter;
+use layout::gv::DotParser;
+use layout::gv::GraphBuilder;
+
+use crate::render::with_chrome::WithChrome;
+
+/// Lay out Graphviz dot diagrams to svg, without a browser.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Graphviz {
+    /// Use the `dot` binary if it is in `PATH`, otherwise the builtin layout.
+    #[default]
+    Auto,
+
+    /// The pure-rust layout from `layout-rs`. It supports a subset of the dot language.
+    Builtin,
+
+    /// The `dot` binary at the given path.
+    Binary(PathBuf),
+}
+
+impl Graphviz {
+    /// Lay out a dot diagram to a standalone svg document.
+    ///
+    /// `line` is the line number in the markdown source where the diagram starts, it is used
+    /// to report errors in the diagram. A `dot` binary that can not run is reported as such.
+    pub fn to_svg(&self, source: &str, line: usize) -> anyhow::Result<String> {
+        match self {
+            Graphviz::Auto => match find_dot() {
+                Some(dot) => Self::run_binary(&PathBuf::from(dot), source, line),
+                None => Self::run_builtin(source, line),
+            },
+            Graphviz::Builtin => Self::run_builtin(source, line),
+            Graphviz::Binary(dot) => Self::run_binary(dot, source, line),
+        }
+    }
+
+    /// Render a dot diagram to an image, such as "png" or "jpg".
+    pub async fn to_image(
+        &self,
+        source: &str,
+        line: usize,
+        output_type: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let svg = self.to_svg(source, line)?;
+        WithChrome::render_markup("image/svg+xml", &svg, output_type, None, None, None).await
+    }
+
+    fn run_builtin(source: &str, line: usize) -> anyhow::Result<String> {
+        let mut parser = DotParser::new(source);
+        let graph = parser
+            .process()
+            .map_err(|e| anyhow::anyhow!("{}", e))
+            .with_context(|| invalid_at(line))?;
+
+        // layout-rs panics on some graphs it does not support instead of returning an error.
+        let svg = panic::catch_unwind(|| {
+            let mut builder = GraphBuilder::new();
+            builder.visit_graph(&graph);
+            let mut visual_graph = builder.get();
+
+            let mut writer = SVGWriter::new();
+            visual_graph.do_it(false, false, false, &mut writer);
+            writer.finalize()
+        })
+        .map_err(|_| anyhow::anyhow!("The builtin layout does not support this graph"))
+        .with_context(|| invalid_at(line))?;
+
+        Ok(svg)
+    }
+
+    fn run_binary(dot: &PathBuf, source: &str, line: usize) -> anyhow::Result<String> {
+        let mut cmd = Command::new(dot);
+        cmd.arg("-Tsvg")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => anyhow::bail!(
+                "dot not found: {}; install Graphviz, such as with `apt install graphviz` or `brew install graphviz`",
+                dot.display()
+            ),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to execute: {:?}", cmd));
+            }
+        };
+
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(source.as_bytes())
+            .with_context(|| format!("Failed to write to stdin of: {:?}", cmd))?;
+
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!(
+                "{} failed: {}",
+                dot.display(),
+                stderr.trim()
+            ))
+            .with_context(|| invalid_at(line));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// The `dot` binary in `PATH`, probing once per process.
+fn find_dot() -> Option<&'static str> {
+    static DOT: OnceLock<Option<String>> = OnceLock::new();
+    DOT.get_or_init(|| WithChrome::find_available_command(&["dot"]).ok())
+        .as_deref()
+}
+
+fn invalid_at(line: usize) -> String {
+    format!("Invalid dot diagram at line {}", line)
+}
+
+/// Strip the xml declaration and doctype so that the svg can be put into html.
+pub(crate) fn strip_xml_prolog(svg: &str) -> &str {
+    svg.find("<svg").map(|i| &svg[i..]).unwrap_or(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_to_svg() {
+        let svg = Graphviz::Builtin
+            .to_svg("digraph { a -> b [label=\"foo\"]; }", 1)
+            .unwrap();
+
+        assert!(strip_xml_prolog(&svg).starts_with("<svg"));
+        assert!(svg.contains("foo"));
+    }
+
+    #[test]
+    fn test_builtin_invalid() {
+        let err = Graphviz::Builtin.to_svg("digraph { a -> }", 7).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid dot diagram at line 7");
+    }
+
+    #[test]
+    fn test_binary_not_found() {
+        let err = Graphviz::Binary(PathBuf::from("/nonexistent/dot"))
+            .to_svg("digraph { a -> b }", 3)
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "dot not found: /nonexistent/dot; install Graphviz, such as with `apt install graphviz` or `brew install graphviz`"
+        );
+    }
+
+    #[test]
+    fn test_binary_failed() {
+        // A `dot` that fails is told the diagram is invalid.
+        let err = Graphviz::Binary(PathBuf::from("false"))
+            .to_svg("digraph { a -> }", 5)
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Invalid dot diagram at line 5");
+        assert!(err
+            .chain()
+            .any(|e| e.to_string().starts_with("false failed")));
+    }
+
+    #[test]
+    fn test_strip_xml_prolog() {
+        let svg = "<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n<svg></svg>";
+        assert_eq!(strip_xml_prolog(svg), "<svg></svg>");
+        assert_eq!(strip_xml_prolog("x"), "x");
+    }
+}
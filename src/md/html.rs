@@ -0,0 +1,506 @@
+//! Serialize a mdast tree to html.
+
+use std::collections::HashMap;
+
+use markdown::mdast::Definition;
+use markdown::mdast::FootnoteDefinition;
+use markdown::mdast::List;
+use markdown::mdast::Node;
+use markdown::mdast::Table;
+
+use crate::md::escape_html;
+use crate::md::walk;
+
+/// Protocols allowed in `<a href>`, the same as the markdown crate allows.
+const HREF_PROTOCOLS: &[&str] = &["http", "https", "irc", "ircs", "mailto", "xmpp"];
+
+/// Protocols allowed in `<img src>`.
+const SRC_PROTOCOLS: &[&str] = &["http", "https"];
+
+/// Serialize a mdast tree to html, the way the markdown crate compiles GFM and math.
+///
+/// `Html` nodes are written as is: the markdown crate escapes raw html in the source unless
+/// `allow_dangerous_html` is set, which [`Pipeline`] does before the transforms run, so that
+/// html added by a transform is kept.
+///
+/// [`Pipeline`]: crate::md::transform::Pipeline
+pub fn to_html(root: &Node) -> String {
+    let mut writer = HtmlWriter::new(root);
+    writer.node(root);
+    writer.footnote_section();
+    writer.out
+}
+
+struct HtmlWriter<'a> {
+    out: String,
+
+    /// Link and image definitions by identifier; the first one wins.
+    definitions: HashMap<&'a str, &'a Definition>,
+
+    footnote_definitions: HashMap<&'a str, &'a FootnoteDefinition>,
+
+    /// Identifiers of referenced footnotes in order of the first reference, and the number of
+    /// references so far.
+    footnote_calls: Vec<(&'a str, usize)>,
+
+    /// Whether paragraphs are written without `<p>`, in a tight list.
+    tight: bool,
+
+    /// Task list checkbox to write at the start of the next paragraph.
+    checkbox: Option<bool>,
+}
+
+impl<'a> HtmlWriter<'a> {
+    fn new(root: &'a Node) -> Self {
+        let mut definitions = HashMap::new();
+        let mut footnote_definitions = HashMap::new();
+
+        walk(root, &mut |node| match node {
+            Node::Definition(d) => {
+                definitions.entry(d.identifier.as_str()).or_insert(d);
+            }
+            Node::FootnoteDefinition(d) => {
+                footnote_definitions
+                    .entry(d.identifier.as_str())
+                    .or_insert(d);
+            }
+            _ => {}
+        });
+
+        Self {
+            out: String::new(),
+            definitions,
+            footnote_definitions,
+            footnote_calls: vec![],
+            tight: false,
+            checkbox: None,
+        }
+    }
+
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    /// Start a new line unless at the start of one.
+    fn cr(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn children(&mut self, node: &'a Node) {
+        if let Some(children) = node.children() {
+            for child in children {
+                self.node(child);
+            }
+        }
+    }
+
+    /// Write the children of a container that resets list tightness, such as a block quote.
+    fn loose_children(&mut self, node: &'a Node) {
+        let tight = std::mem::replace(&mut self.tight, false);
+        self.children(node);
+        self.tight = tight;
+    }
+
+    fn node(&mut self, node: &'a Node) {
+        match node {
+            Node::Root(_) => {
+                self.children(node);
+                self.cr();
+            }
+            Node::Paragraph(_) => {
+                if self.tight {
+                    self.write_checkbox();
+                    self.children(node);
+                } else {
+                    self.cr();
+                    self.push("<p>");
+                    self.write_checkbox();
+                    self.children(node);
+                    self.push("</p>");
+                    self.cr();
+                }
+            }
+            Node::Heading(h) => {
+                self.cr();
+                self.push(&format!("<h{}>", h.depth));
+                self.children(node);
+                self.push(&format!("</h{}>", h.depth));
+                self.cr();
+            }
+            Node::ThematicBreak(_) => {
+                self.cr();
+                self.push("<hr />");
+                self.cr();
+            }
+            Node::Blockquote(_) => {
+                self.cr();
+                self.push("<blockquote>");
+                self.cr();
+                self.loose_children(node);
+                self.cr();
+                self.push("</blockquote>");
+                self.cr();
+            }
+            Node::Code(code) => {
+                let class = code
+                    .lang
+                    .as_deref()
+                    .map(|lang| format!(" class=\"language-{}\"", escape_html(lang)));
+                self.code_block(class.as_deref().unwrap_or_default(), &code.value);
+            }
+            Node::Math(math) => {
+                self.code_block(" class=\"language-math math-display\"", &math.value);
+            }
+            Node::List(list) => self.list(node, list),
+            Node::ListItem(item) => {
+                self.cr();
+                self.push("<li>");
+                if let Some(checked) = item.checked {
+                    self.checkbox = Some(checked);
+                    if !matches!(item.children.first(), Some(Node::Paragraph(_))) {
+                        self.write_checkbox();
+                    }
+                }
+                self.children(node);
+                self.push("</li>");
+                self.cr();
+            }
+            Node::Table(table) => self.table(table),
+            Node::Html(html) => self.push(&html.value),
+            Node::Definition(_) | Node::FootnoteDefinition(_) | Node::Yaml(_) | Node::Toml(_) => {}
+
+            Node::Text(text) => self.push(&escape_html(&text.value)),
+            Node::Emphasis(_) => self.wrap("em", node),
+            Node::Strong(_) => self.wrap("strong", node),
+            Node::Delete(_) => self.wrap("del", node),
+            Node::InlineCode(code) => {
+                self.push(&format!("<code>{}</code>", escape_html(&code.value)));
+            }
+            Node::InlineMath(math) => {
+                self.push(&format!(
+                    "<code class=\"language-math math-inline\">{}</code>",
+                    escape_html(&math.value)
+                ));
+            }
+            Node::Break(_) => self.push("<br />\n"),
+            Node::Link(link) => self.link(node, &link.url, link.title.as_deref()),
+            Node::Image(image) => self.image(&image.url, &image.alt, image.title.as_deref()),
+            Node::LinkReference(r) => match self.definitions.get(r.identifier.as_str()) {
+                Some(def) => self.link(node, &def.url, def.title.as_deref()),
+                None => self.children(node),
+            },
+            Node::ImageReference(r) => match self.definitions.get(r.identifier.as_str()) {
+                Some(def) => self.image(&def.url, &r.alt, def.title.as_deref()),
+                None => self.push(&escape_html(&r.alt)),
+            },
+            Node::FootnoteReference(r) => self.footnote_reference(&r.identifier),
+
+            // mdx nodes are not parsed with the options of this crate.
+            _ => self.children(node),
+        }
+    }
+
+    fn wrap(&mut self, tag: &str, node: &'a Node) {
+        self.push(&format!("<{}>", tag));
+        self.children(node);
+        self.push(&format!("</{}>", tag));
+    }
+
+    fn write_checkbox(&mut self) {
+        match self.checkbox.take() {
+            Some(true) => self.push(r#"<input type="checkbox" disabled="" checked="" /> "#),
+            Some(false) => self.push(r#"<input type="checkbox" disabled="" /> "#),
+            None => {}
+        }
+    }
+
+    fn code_block(&mut self, class: &str, value: &str) {
+        self.cr();
+        self.push(&format!("<pre><code{}>", class));
+        if !value.is_empty() {
+            self.push(&escape_html(value));
+            self.push("\n");
+        }
+        self.push("</code></pre>");
+        self.cr();
+    }
+
+    fn list(&mut self, node: &'a Node, list: &List) {
+        let tag = if list.ordered { "ol" } else { "ul" };
+
+        self.cr();
+        match list.start {
+            Some(start) if list.ordered && start != 1 => {
+                self.push(&format!("<ol start=\"{}\">", start))
+            }
+            _ => self.push(&format!("<{}>", tag)),
+        }
+        self.cr();
+
+        let loose = list.spread
+            || list
+                .children
+                .iter()
+                .any(|item| matches!(item, Node::ListItem(item) if item.spread));
+        let tight = std::mem::replace(&mut self.tight, !loose);
+        self.children(node);
+        self.tight = tight;
+
+        self.cr();
+        self.push(&format!("</{}>", tag));
+        self.cr();
+    }
+
+    fn table(&mut self, table: &'a Table) {
+        self.cr();
+        self.push("<table>");
+        self.cr();
+
+        for (i, row) in table.children.iter().enumerate() {
+            if i == 0 {
+                self.push("<thead>");
+                self.cr();
+            } else if i == 1 {
+                self.push("<tbody>");
+                self.cr();
+            }
+
+            self.push("<tr>");
+            self.cr();
+            let cells = row.children().map(|c| c.as_slice()).unwrap_or_default();
+            for (column, cell) in cells.iter().enumerate() {
+                let tag = if i == 0 { "th" } else { "td" };
+                let align = match table.align.get(column) {
+                    Some(markdown::mdast::AlignKind::Left) => " align=\"left\"",
+                    Some(markdown::mdast::AlignKind::Right) => " align=\"right\"",
+                    Some(markdown::mdast::AlignKind::Center) => " align=\"center\"",
+                    _ => "",
+                };
+                self.push(&format!("<{}{}>", tag, align));
+                self.children(cell);
+                self.push(&format!("</{}>", tag));
+                self.cr();
+            }
+            self.push("</tr>");
+            self.cr();
+
+            if i == 0 {
+                self.push("</thead>");
+                self.cr();
+            }
+        }
+
+        if table.children.len() > 1 {
+            self.push("</tbody>");
+            self.cr();
+        }
+        self.push("</table>");
+        self.cr();
+    }
+
+    fn link(&mut self, node: &'a Node, url: &str, title: Option<&str>) {
+        self.push(&format!(
+            "<a href=\"{}\"{}>",
+            sanitize_url(url, HREF_PROTOCOLS),
+            title_attr(title)
+        ));
+        self.children(node);
+        self.push("</a>");
+    }
+
+    fn image(&mut self, url: &str, alt: &str, title: Option<&str>) {
+        self.push(&format!(
+            "<img src=\"{}\" alt=\"{}\"{} />",
+            sanitize_url(url, SRC_PROTOCOLS),
+            escape_html(alt),
+            title_attr(title)
+        ));
+    }
+
+    fn footnote_reference(&mut self, identifier: &'a str) {
+        if !self.footnote_definitions.contains_key(identifier) {
+            return;
+        }
+
+        let index = match self
+            .footnote_calls
+            .iter()
+            .position(|(id, _)| *id == identifier)
+        {
+            Some(index) => index,
+            None => {
+                self.footnote_calls.push((identifier, 0));
+                self.footnote_calls.len() - 1
+            }
+        };
+        self.footnote_calls[index].1 += 1;
+        let calls = self.footnote_calls[index].1;
+
+        let id = footnote_id(identifier);
+        let suffix = if calls > 1 {
+            format!("-{}", calls)
+        } else {
+            String::new()
+        };
+        self.push(&format!(
+            "<sup><a href=\"#user-content-fn-{id}\" id=\"user-content-fnref-{id}{suffix}\" data-footnote-ref=\"\" aria-describedby=\"footnote-label\">{}</a></sup>",
+            index + 1,
+        ));
+    }
+
+    /// Write the referenced footnotes at the end of the document, as GitHub does.
+    fn footnote_section(&mut self) {
+        if self.footnote_calls.is_empty() {
+            return;
+        }
+
+        self.cr();
+        self.push(r#"<section data-footnotes="" class="footnotes"><h2 id="footnote-label" class="sr-only">Footnotes</h2>"#);
+        self.push("\n<ol>");
+
+        // Footnotes may reference other footnotes, which are appended while writing.
+        let mut index = 0;
+        while index < self.footnote_calls.len() {
+            let (identifier, _) = self.footnote_calls[index];
+            let definition = self.footnote_definitions[identifier];
+
+            let out = std::mem::take(&mut self.out);
+            let tight = std::mem::replace(&mut self.tight, false);
+            for child in &definition.children {
+                self.node(child);
+            }
+            self.tight = tight;
+            let content = std::mem::replace(&mut self.out, out);
+
+            let id = footnote_id(identifier);
+            let calls = self.footnote_calls[index].1;
+            let backrefs = (1..=calls)
+                .map(|n| {
+                    let (suffix, sup) = if n > 1 {
+                        (format!("-{}", n), format!("<sup>{}</sup>", n))
+                    } else {
+                        (String::new(), String::new())
+                    };
+                    format!("<a href=\"#user-content-fnref-{id}{suffix}\" data-footnote-backref=\"\" aria-label=\"Back to content\" class=\"data-footnote-backref\">↩{sup}</a>")
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            self.push(&format!("\n<li id=\"user-content-fn-{}\">\n", id));
+            let trimmed = content.trim_end_matches(['\n', '\r']);
+            match trimmed.strip_suffix("</p>") {
+                Some(before) => {
+                    self.push(&format!(
+                        "{} {}</p>{}",
+                        before,
+                        backrefs,
+                        &content[trimmed.len()..]
+                    ));
+                }
+                None => {
+                    self.push(&content);
+                    self.cr();
+                    self.push(&backrefs);
+                }
+            }
+            self.cr();
+            self.push("</li>");
+
+            index += 1;
+        }
+
+        self.push("\n</ol>\n</section>\n");
+    }
+}
+
+fn title_attr(title: Option<&str>) -> String {
+    match title {
+        Some(title) => format!(" title=\"{}\"", escape_html(title)),
+        None => String::new(),
+    }
+}
+
+fn footnote_id(identifier: &str) -> String {
+    markdown::sanitize(&identifier.to_lowercase())
+}
+
+/// Percent-encode a url and escape it for an attribute; drop it if it has a protocol not in
+/// `protocols`, such as `javascript:`.
+fn sanitize_url(url: &str, protocols: &[&str]) -> String {
+    let url = markdown::sanitize(url);
+
+    let end = url.find(['?', '#', '/']).unwrap_or(url.len());
+    if let Some(colon) = url[..end].find(':') {
+        let protocol = url[..colon].to_lowercase();
+        if !protocols.contains(&protocol.as_str()) {
+            return String::new();
+        }
+    }
+
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::options;
+    use crate::md::parse;
+    use crate::md::transform::escape_raw_html;
+
+    /// The serializer produces the same html as the markdown crate.
+    #[test]
+    fn test_same_as_markdown_crate() {
+        let cases = [
+            "# Title\n\nSome *em* **strong** ~~del~~ `code` text.\nNext line  \nhard break\n",
+            "> quote\n> more\n\n---\n\n```rust\nfn main() {}\n```\n\n```\n```\n",
+            "- a\n- b\n  - nested\n- c\n",
+            "1. one\n\n2. two\n",
+            "3. three\n4. four\n",
+            "- [ ] todo\n- [x] done\n",
+            "- [x] loose\n\n- para\n\n  more\n",
+            "| a | b | c |\n|:--|:-:|--:|\n| 1 | 2 | 3 |\n",
+            "| a |\n| - |\n",
+            "[link](https://a.com \"T\") ![img](x.png \"T\") <https://b.com> [js](javascript:alert(1))\n",
+            "[ref][r] ![im][r] [r]\n\n[r]: https://r.com/a b \"R\"\n",
+            "<div>raw</div>\n\ninline <span>html</span> & \"quotes\"\n",
+            "Math $x^2$ and\n\n$$\n\\sum\n$$\n",
+            "Note[^1] and[^b] again[^1].\n\n[^1]: First.\n[^b]: Second\n\n    with more.\n",
+            "https://auto.link and www.example.com\n",
+            "- > quote in list\n-     indented code\n-\n- ```\n  fenced\n  ```\n",
+            "![*alt* `x`](a.png) &copy; &amp; \\* [a\nb](c)\n",
+            "- a\n\n  - b\n  - c\n",
+            "Setext\n===\n\n* * *\n",
+        ];
+
+        for md in cases {
+            let want = markdown::to_html_with_options(md, &options()).unwrap();
+            let mut root = parse(md).unwrap();
+            escape_raw_html(&mut root);
+            let got = to_html(&root);
+            assert_eq!(got, want, "markdown: {:?}", md);
+        }
+    }
+
+    #[test]
+    fn test_html_nodes_are_kept() {
+        let mut root = parse("a\n").unwrap();
+        root.children_mut()
+            .unwrap()
+            .push(Node::Html(markdown::mdast::Html {
+                value: "<hr class=\"x\">".to_string(),
+                position: None,
+            }));
+
+        assert_eq!(to_html(&root), "<p>a</p>\n<hr class=\"x\">\n");
+    }
+
+    #[test]
+    fn test_sanitize_url() {
+        assert_eq!(sanitize_url("a b.png", SRC_PROTOCOLS), "a%20b.png");
+        assert_eq!(sanitize_url("data:image/png;base64,AA", SRC_PROTOCOLS), "");
+        assert_eq!(sanitize_url("mailto:a@b.c", HREF_PROTOCOLS), "mailto:a@b.c");
+        assert_eq!(sanitize_url("./a:b", SRC_PROTOCOLS), "./a:b");
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Context;
+
+use crate::md::data_url;
+use crate::md::escape_html;
+use crate::render::with_chrome::WithChrome;
+
+/// How formulas (`$…$` and `$$…$$`) are written into the output.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MathOutput {
+    /// Keep the `<code class="language-math">` markup produced by the markdown crate.
+    #[default]
+    Source,
+
+    /// Convert to MathML, which browsers render natively.
+    MathMl,
+
+    /// Render to an image of the given type, such as "png" or "jpg", and embed it.
+    Image { format: String },
+}
+
+impl MathOutput {
+    /// Build the html that replaces a formula.
+    pub async fn render_html(&self, tex: &str, display: bool) -> anyhow::Result<String> {
+        let class = if display {
+            "math-display"
+        } else {
+            "math-inline"
+        };
+
+        match self {
+            MathOutput::Source => {
+                let code = format!(
+                    r#"<code class="language-math {}">{}</code>"#,
+                    class,
+                    escape_html(tex)
+                );
+                if display {
+                    Ok(format!("<pre>{}</pre>", code))
+                } else {
+                    Ok(code)
+                }
+            }
+            MathOutput::MathMl => Math::to_mathml(tex, display),
+            MathOutput::Image { format } => {
+                let data = Math::to_image(tex, display, format).await?;
+                let img = format!(
+                    r#"<img class="{}" alt="{}" src="{}" />"#,
+                    class,
+                    escape_html(tex),
+                    data_url(format, &data)
+                );
+
+                if display {
+                    Ok(format!(r#"<div class="{}">{}</div>"#, class, img))
+                } else {
+                    Ok(img)
+                }
+            }
+        }
+    }
+}
+
+/// Render TeX formulas with KaTeX.
+///
+/// KaTeX is bundled with the `katex` crate and runs in an embedded js engine, thus no
+/// network access is required.
+pub struct Math;
+
+impl Math {
+    /// Convert a TeX formula to MathML.
+    ///
+    /// `display` specifies whether it is a display (block) formula or an inline one.
+    pub fn to_mathml(tex: &str, display: bool) -> anyhow::Result<String> {
+        let opts = katex::Opts::builder()
+            .display_mode(display)
+            .output_type(katex::OutputType::Mathml)
+            .throw_on_error(true)
+            .build()
+            .context("Failed to build KaTeX options")?;
+
+        katex::render_with_opts(tex, &opts)
+            .with_context(|| format!("Failed to render formula: {}", tex))
+    }
+
+    /// Render a TeX formula to an image with headless chrome.
+    ///
+    /// # Arguments
+    ///
+    /// * `tex` - the TeX source of the formula
+    /// * `display` - whether it is a display formula
+    /// * `output_type` - specifies output image type such as "png", "jpg"
+    pub async fn to_image(tex: &str, display: bool, output_type: &str) -> anyhow::Result<Vec<u8>> {
+        let page = Self::build_page(&Self::to_mathml(tex, display)?);
+        WithChrome::render_markup("text/html", &page, output_type, None, None, None).await
+    }
+
+    /// Build a html page that contains only the formula, with some padding to trim.
+    fn build_page(mathml: &str) -> String {
+        format!(
+            r#"<html><body style="margin: 0;"><div style="display: inline-block; padding: 4px; font-size: 20px;">{}</div></body></html>"#,
+            mathml
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_mathml() {
+        let mathml = Math::to_mathml("E=mc^2", false).unwrap();
+        assert!(mathml.starts_with(r#"<span class="katex"><math"#));
+        assert!(mathml.contains("<mi>E</mi>"));
+
+        let mathml = Math::to_mathml("x", true).unwrap();
+        assert!(mathml.contains(r#"display="block""#));
+    }
+
+    #[test]
+    fn test_to_mathml_invalid() {
+        let err = Math::to_mathml(r"\frac{", false).unwrap_err();
+        assert!(err.to_string().contains(r"\frac{"));
+    }
+
+    #[tokio::test]
+    async fn test_render_html_source() {
+        let html = MathOutput::Source.render_html("a<b", true).await.unwrap();
+        assert_eq!(
+            html,
+            r#"<pre><code class="language-math math-display">a&lt;b</code></pre>"#
+        );
+    }
+}
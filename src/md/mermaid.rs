@@ -0,0 +1,232 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use tempfile::TempDir;
+
+use crate::md::escape_html;
+use crate::md::unescape_html;
+use crate::render::dom_body;
+use crate::render::with_chrome::WithChrome;
+
+/// Prefix of the page body when mermaid produced a svg.
+const SVG_MARK: &str = "XPMD-MERMAID-SVG:";
+
+/// Prefix of the page body when mermaid failed.
+const ERROR_MARK: &str = "XPMD-MERMAID-ERROR:";
+
+/// The mermaid.js vendored when xpmd was built, empty if it was not fetched.
+static VENDORED_JS: &str = include_str!(concat!(env!("OUT_DIR"), "/mermaid.min.js"));
+
+/// Characters to escape in a path used as a `file://` url.
+const FILE_URL_PATH: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?');
+
+/// Render mermaid diagrams with a local mermaid.js in headless chrome.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mermaid {
+    /// The mermaid.js to load; `None` for the one embedded in the binary.
+    js_path: Option<PathBuf>,
+}
+
+impl Default for Mermaid {
+    fn default() -> Self {
+        Self::vendored()
+    }
+}
+
+impl Mermaid {
+    /// Use the mermaid.js at `js_path`.
+    pub fn new(js_path: impl Into<PathBuf>) -> Self {
+        Self {
+            js_path: Some(js_path.into()),
+        }
+    }
+
+    /// Use the mermaid.js vendored in this repo and embedded in the binary when it was built,
+    /// see `vendor/README.md`.
+    pub fn vendored() -> Self {
+        Self { js_path: None }
+    }
+
+    /// The mermaid.js to load; `None` for the embedded one.
+    pub fn js_path(&self) -> Option<&Path> {
+        self.js_path.as_deref()
+    }
+
+    /// Render a mermaid diagram to a standalone svg document.
+    ///
+    /// `line` is the line number in the markdown source where the diagram starts, it is used
+    /// to report the location of syntax errors.
+    pub async fn to_svg(&self, source: &str, line: usize) -> anyhow::Result<String> {
+        // The embedded mermaid.js is written out for chrome to load, and removed when dropped.
+        let temp_dir;
+        let js_path = match &self.js_path {
+            Some(path) => {
+                if !path.exists() {
+                    anyhow::bail!(
+                        "mermaid.js not found: {}; see vendor/README.md to fetch it",
+                        path.display()
+                    );
+                }
+                path.clone()
+            }
+            None => {
+                if VENDORED_JS.is_empty() {
+                    anyhow::bail!(
+                        "mermaid.js not found: it was not vendored when xpmd was built; see vendor/README.md to fetch it"
+                    );
+                }
+                temp_dir = TempDir::new()?;
+                let path = temp_dir.path().join("mermaid.min.js");
+                fs::write(&path, VENDORED_JS)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                path
+            }
+        };
+
+        let page = Self::build_page(&js_path, source);
+        let dom = WithChrome::dump_dom("text/html", &page, None, None).await?;
+
+        Self::parse_dom(&dom, line)
+    }
+
+    /// Render a mermaid diagram to an image, such as "png" or "jpg".
+    pub async fn to_image(
+        &self,
+        source: &str,
+        line: usize,
+        output_type: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let svg = self.to_svg(source, line).await?;
+        let page = format!(r#"<html><body style="margin: 0;">{}</body></html>"#, svg);
+        WithChrome::render_markup("text/html", &page, output_type, None, None, None).await
+    }
+
+    /// Build a page that renders the diagram and replaces the body with the result.
+    ///
+    /// The svg is serialized as xml so that it is a valid standalone svg document.
+    fn build_page(js_path: &Path, source: &str) -> String {
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head><script src="file://{js}"></script></head>
+<body>
+<pre id="source">{source}</pre>
+<script>
+(async () => {{
+  let result;
+  try {{
+    mermaid.initialize({{ startOnLoad: false, htmlLabels: false, flowchart: {{ htmlLabels: false }} }});
+    const source = document.getElementById("source").textContent;
+    const {{ svg }} = await mermaid.render("xpmd-mermaid", source);
+    const doc = new DOMParser().parseFromString(svg, "image/svg+xml");
+    result = "{svg_mark}" + new XMLSerializer().serializeToString(doc.documentElement);
+  }} catch (e) {{
+    result = "{error_mark}" + String((e && e.message) || e);
+  }}
+  document.body.textContent = result;
+}})();
+</script>
+</body>
+</html>"#,
+            js = percent_encoding::utf8_percent_encode(&js_path.to_string_lossy(), FILE_URL_PATH),
+            source = escape_html(source),
+            svg_mark = SVG_MARK,
+            error_mark = ERROR_MARK,
+        )
+    }
+
+    /// Extract the svg or the error from the DOM dumped by chrome.
+    fn parse_dom(dom: &str, line: usize) -> anyhow::Result<String> {
+        let body = dom_body(dom);
+
+        if let Some(svg) = body.strip_prefix(SVG_MARK) {
+            return Ok(unescape_html(svg));
+        }
+
+        if let Some(message) = body.strip_prefix(ERROR_MARK) {
+            let message = unescape_html(message);
+            let line = line + Self::error_line(&message).unwrap_or_default();
+            anyhow::bail!("Invalid mermaid diagram at line {}: {}", line, message);
+        }
+
+        anyhow::bail!(
+            "Mermaid did not finish rendering the diagram at line {}",
+            line
+        )
+    }
+
+    /// Parse the 1-based line number in messages like "Parse error on line 2: ...".
+    fn error_line(message: &str) -> Option<usize> {
+        let (_, rest) = message.split_once("on line ")?;
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_page_escapes_source() {
+        let page = Mermaid::build_page(Path::new("/x y/#1/mermaid.min.js"), "graph TD\nA-->B<br>");
+
+        assert!(page.contains(r#"<script src="file:///x%20y/%231/mermaid.min.js"></script>"#));
+        assert!(page.contains("<pre id=\"source\">graph TD\nA--&gt;B&lt;br&gt;</pre>"));
+    }
+
+    #[test]
+    fn test_parse_dom_svg() {
+        let dom = format!(
+            "<html><head></head><body>{}&lt;svg xmlns=\"http://www.w3.org/2000/svg\"&gt;&lt;g&gt;a &amp;amp; b&lt;/g&gt;&lt;/svg&gt;</body></html>\n",
+            SVG_MARK
+        );
+
+        let svg = Mermaid::parse_dom(&dom, 3).unwrap();
+        assert_eq!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><g>a &amp; b</g></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_parse_dom_error() {
+        let dom = format!(
+            "<html><head></head><body>{}Parse error on line 2:\n...</body></html>",
+            ERROR_MARK
+        );
+
+        let err = Mermaid::parse_dom(&dom, 10).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Invalid mermaid diagram at line 12: Parse error on line 2:"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_dom_unfinished() {
+        let err = Mermaid::parse_dom("<html><body><pre>x</pre></body></html>", 5).unwrap_err();
+        assert!(err.to_string().contains("line 5"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_js() {
+        let err = Mermaid::new("/nonexistent/mermaid.min.js")
+            .to_svg("graph TD\nA-->B", 1)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("mermaid.js not found"));
+    }
+}
@@ -0,0 +1,422 @@
+pub mod commonmark;
+pub mod graphviz;
+pub mod html;
+pub mod math;
+pub mod mermaid;
+pub(crate) mod splice;
+pub mod transform;
+
+use base64::Engine;
+pub use commonmark::to_markdown;
+pub use commonmark::to_markdown_preserving;
+use graphviz::strip_xml_prolog;
+pub use graphviz::Graphviz;
+pub use html::to_html;
+use markdown::mdast::Html;
+use markdown::mdast::Node;
+pub use math::MathOutput;
+pub use mermaid::Mermaid;
+use transform::async_trait;
+use transform::replace_nodes;
+use transform::select_nodes;
+use transform::ConvertAlerts;
+use transform::Pipeline;
+use transform::Transform;
+
+/// Markdown parse options used by this crate: GFM plus math (`$…$` and `$$…$$`) and front
+/// matter.
+pub fn parse_options() -> markdown::ParseOptions {
+    let mut parse = markdown::ParseOptions::gfm();
+    parse.constructs.math_text = true;
+    parse.constructs.math_flow = true;
+    parse.constructs.frontmatter = true;
+    parse
+}
+
+/// Markdown to html options used by this crate, see [`parse_options`].
+pub fn options() -> markdown::Options {
+    markdown::Options {
+        parse: parse_options(),
+        compile: markdown::CompileOptions::gfm(),
+    }
+}
+
+/// Parse markdown into a mdast tree with [`parse_options`].
+pub fn parse(md: &str) -> anyhow::Result<Node> {
+    markdown::to_mdast(md, &parse_options()).map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Visit `node` and all its descendants in document order.
+pub(crate) fn walk<'a>(node: &'a Node, f: &mut impl FnMut(&'a Node)) {
+    f(node);
+    if let Some(children) = node.children() {
+        for child in children {
+            walk(child, f);
+        }
+    }
+}
+
+/// Visit `node` and all its descendants in document order, allowing changes.
+///
+/// The children of a node are visited after `f` is called on it, thus the children `f` puts in
+/// are visited too.
+pub fn walk_mut(node: &mut Node, f: &mut impl FnMut(&mut Node)) {
+    f(node);
+    if let Some(children) = node.children_mut() {
+        for child in children {
+            walk_mut(child, f);
+        }
+    }
+}
+
+/// Escape text so that it can be embedded in html content or attribute values.
+pub(crate) fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse [`escape_html`], and the `&nbsp;` chrome emits when serializing a DOM.
+pub(crate) fn unescape_html(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
+/// Build a `data:` url that embeds image data of type `format`, such as "png".
+pub(crate) fn data_url(format: &str, data: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        crate::Mime::get_or_fallback(format),
+        base64::engine::general_purpose::STANDARD.encode(data)
+    )
+}
+
+/// How diagram code blocks, such as ```` ```mermaid ```` or ```` ```dot ````, are written into
+/// the output.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DiagramOutput {
+    /// Keep the code block as is.
+    #[default]
+    Source,
+
+    /// Replace the code block with an inline svg.
+    Svg,
+
+    /// Render to an image of the given type, such as "png" or "jpg", and embed it.
+    Image { format: String },
+}
+
+/// Convert markdown to html.
+///
+/// Constructs that the markdown crate only emits as plain code, such as formulas, are
+/// converted according to the output settings.
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownToHtml {
+    /// How formulas are written into the html.
+    pub math: MathOutput,
+
+    /// How ```` ```mermaid ```` code blocks are written into the html.
+    pub mermaid: DiagramOutput,
+
+    /// Renders mermaid diagrams, with the vendored mermaid.js by default.
+    pub mermaid_renderer: Mermaid,
+
+    /// How ```` ```dot ```` and ```` ```graphviz ```` code blocks are written into the html.
+    pub dot: DiagramOutput,
+
+    /// Lays out dot diagrams.
+    pub dot_renderer: Graphviz,
+
+    /// Keep raw html in the markdown source. It is escaped by default.
+    pub allow_dangerous_html: bool,
+}
+
+impl MarkdownToHtml {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_math(mut self, math: MathOutput) -> Self {
+        self.math = math;
+        self
+    }
+
+    pub fn with_mermaid(mut self, mermaid: DiagramOutput) -> Self {
+        self.mermaid = mermaid;
+        self
+    }
+
+    pub fn with_mermaid_renderer(mut self, renderer: Mermaid) -> Self {
+        self.mermaid_renderer = renderer;
+        self
+    }
+
+    pub fn with_dot(mut self, dot: DiagramOutput) -> Self {
+        self.dot = dot;
+        self
+    }
+
+    pub fn with_dot_renderer(mut self, renderer: Graphviz) -> Self {
+        self.dot_renderer = renderer;
+        self
+    }
+
+    pub fn with_dangerous_html(mut self, allow: bool) -> Self {
+        self.allow_dangerous_html = allow;
+        self
+    }
+
+    /// Render markdown source to html.
+    pub async fn render(&self, md: &str) -> anyhow::Result<String> {
+        self.pipeline().run(md).await
+    }
+
+    /// Build a pipeline that converts markdown with these settings. Add transforms to it to
+    /// customize the conversion.
+    pub fn pipeline(&self) -> Pipeline {
+        Pipeline::new()
+            .with_dangerous_html(self.allow_dangerous_html)
+            .with(ConvertAlerts::new().with_dangerous_html(self.allow_dangerous_html))
+            .with(RenderNodes(self.clone()))
+    }
+
+    /// Whether a node is converted rather than written as the markdown crate does.
+    fn is_rendered(&self, node: &Node) -> bool {
+        match node {
+            Node::InlineMath(_) | Node::Math(_) => self.math != MathOutput::Source,
+            Node::Code(code) => self.diagram_output(code.lang.as_deref()) != &DiagramOutput::Source,
+            _ => false,
+        }
+    }
+
+    /// Build the html that replaces a node selected by [`Self::is_rendered`].
+    async fn render_node(&self, node: &Node) -> anyhow::Result<String> {
+        match node {
+            Node::InlineMath(m) => self.math.render_html(&m.value, false).await,
+            Node::Math(m) => self.math.render_html(&m.value, true).await,
+            Node::Code(code) => {
+                let lang = code.lang.as_deref().unwrap_or_default();
+                let line = node.position().map(|p| p.start.line).unwrap_or_default();
+                self.render_diagram(lang, &code.value, line).await
+            }
+            _ => unreachable!("only selected nodes are rendered"),
+        }
+    }
+
+    /// How a code block with the language `lang` is written into the html.
+    fn diagram_output(&self, lang: Option<&str>) -> &DiagramOutput {
+        match lang {
+            Some("mermaid") => &self.mermaid,
+            Some("dot") | Some("graphviz") => &self.dot,
+            _ => &DiagramOutput::Source,
+        }
+    }
+
+    /// Build the html that replaces a diagram code block starting at `line`.
+    async fn render_diagram(
+        &self,
+        lang: &str,
+        source: &str,
+        line: usize,
+    ) -> anyhow::Result<String> {
+        let mermaid = lang == "mermaid";
+
+        match self.diagram_output(Some(lang)) {
+            DiagramOutput::Source => unreachable!("source code blocks are not collected"),
+            DiagramOutput::Svg => {
+                let svg = if mermaid {
+                    self.mermaid_renderer.to_svg(source, line).await?
+                } else {
+                    self.dot_renderer.to_svg(source, line)?
+                };
+                Ok(format!(
+                    r#"<div class="{}">{}</div>"#,
+                    lang,
+                    strip_xml_prolog(&svg)
+                ))
+            }
+            DiagramOutput::Image { format } => {
+                let data = if mermaid {
+                    self.mermaid_renderer.to_image(source, line, format).await?
+                } else {
+                    self.dot_renderer.to_image(source, line, format).await?
+                };
+                Ok(format!(
+                    r#"<p><img class="{}" alt="{} diagram" src="{}" /></p>"#,
+                    lang,
+                    lang,
+                    data_url(format, &data)
+                ))
+            }
+        }
+    }
+}
+
+/// Replaces formulas and diagrams with html according to the [`MarkdownToHtml`] settings.
+struct RenderNodes(MarkdownToHtml);
+
+#[async_trait]
+impl Transform for RenderNodes {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        let select = |node: &Node| self.0.is_rendered(node);
+
+        let mut rendered = vec![];
+        for node in select_nodes(root, &select) {
+            rendered.push(Node::Html(Html {
+                value: self.0.render_node(node).await?,
+                position: node.position().cloned(),
+            }));
+        }
+
+        replace_nodes(root, &select, &mut rendered.into_iter());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_math() {
+        let root = parse("Energy: $E=mc^2$\n\n$$\n\\sum_i x_i\n$$\n").unwrap();
+
+        let mut found = vec![];
+        walk(&root, &mut |node| match node {
+            Node::InlineMath(m) => found.push(format!("inline:{}", m.value)),
+            Node::Math(m) => found.push(format!("display:{}", m.value)),
+            _ => {}
+        });
+
+        assert_eq!(found, vec!["inline:E=mc^2", "display:\\sum_i x_i"]);
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html(r#"a<b>&"c""#), "a&lt;b&gt;&amp;&quot;c&quot;");
+    }
+
+    #[test]
+    fn test_unescape_html() {
+        assert_eq!(
+            unescape_html("a&lt;b&gt;&amp;amp;&quot;&nbsp;"),
+            "a<b>&amp;\"\u{a0}"
+        );
+    }
+
+    #[test]
+    fn test_data_url() {
+        assert_eq!(data_url("png", b"abc"), "data:image/png;base64,YWJj");
+    }
+
+    #[tokio::test]
+    async fn test_render_mermaid_source() {
+        let html = MarkdownToHtml::new()
+            .render("```mermaid\ngraph TD\n```\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-mermaid\">graph TD\n</code></pre>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_mermaid_missing_js() {
+        let err = MarkdownToHtml::new()
+            .with_mermaid(DiagramOutput::Svg)
+            .with_mermaid_renderer(Mermaid::new("/nonexistent/mermaid.min.js"))
+            .render("```mermaid\ngraph TD\n```\n")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("mermaid.js not found"));
+    }
+
+    #[tokio::test]
+    async fn test_render_dot_svg() {
+        let html = MarkdownToHtml::new()
+            .with_dot(DiagramOutput::Svg)
+            .with_dot_renderer(Graphviz::Builtin)
+            .render(
+                "# Arch\n\n```dot\ndigraph { a -> b }\n```\n\n```graphviz\ndigraph { c; }\n```\n",
+            )
+            .await
+            .unwrap();
+
+        assert!(
+            html.starts_with("<h1>Arch</h1>\n<div class=\"dot\"><svg"),
+            "{}",
+            html
+        );
+        assert!(html.contains("<div class=\"graphviz\"><svg"));
+        assert!(!html.contains("<code"));
+    }
+
+    #[tokio::test]
+    async fn test_render_dot_invalid() {
+        let err = MarkdownToHtml::new()
+            .with_dot(DiagramOutput::Svg)
+            .with_dot_renderer(Graphviz::Builtin)
+            .render("text\n\n```dot\ndigraph {\n```\n")
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Invalid dot diagram at line 3");
+    }
+
+    #[tokio::test]
+    async fn test_render_math_source() {
+        let html = MarkdownToHtml::new().render("$x$").await.unwrap();
+        assert_eq!(
+            html,
+            "<p><code class=\"language-math math-inline\">x</code></p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_math_mathml() {
+        let html = MarkdownToHtml::new()
+            .with_math(MathOutput::MathMl)
+            .render("Energy: $E=mc^2$ and\n\n$$\nx^2\n$$\n\n- in a list $y$\n")
+            .await
+            .unwrap();
+
+        assert!(
+            html.starts_with("<p>Energy: <span class=\"katex\"><math"),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains(r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="block">"#)
+        );
+        assert!(html.contains("<li>in a list <span class=\"katex\"><math"));
+        assert!(!html.contains("<p><span class=\"katex-display\">"));
+        assert!(!html.contains("XPMD"));
+    }
+
+    #[tokio::test]
+    async fn test_render_alert_math() {
+        let html = MarkdownToHtml::new()
+            .with_math(MathOutput::MathMl)
+            .render("!!! warning\n\n    $x$\n")
+            .await
+            .unwrap();
+
+        assert!(
+            html.starts_with("<div class=\"markdown-alert markdown-alert-warning\">\n<p class=\"markdown-alert-title\">Warning</p>\n<p><span class=\"katex\"><math"),
+            "{}",
+            html
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use markdown::unist::Position;
+
+/// Replace byte ranges of the markdown source, keeping everything else as is.
+pub(crate) struct Splice<'a> {
+    source: &'a str,
+    /// Source byte range to replace and the text to put in.
+    edits: Vec<(usize, usize, String)>,
+}
+
+impl<'a> Splice<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            edits: vec![],
+        }
+    }
+
+    /// Replace the source of a node with `text`.
+    pub(crate) fn replace(&mut self, position: &Position, text: impl Into<String>) {
+        self.edits
+            .push((position.start.offset, position.end.offset, text.into()));
+    }
+
+    /// Build the new source with all the replacements applied.
+    pub(crate) fn apply(mut self) -> anyhow::Result<String> {
+        self.edits.sort_by_key(|(start, _, _)| *start);
+
+        let mut out = String::with_capacity(self.source.len());
+        let mut prev_end = 0;
+
+        for (start, end, text) in self.edits {
+            if start < prev_end {
+                anyhow::bail!("Overlapping replacement at byte offset {}", start);
+            }
+            out.push_str(&self.source[prev_end..start]);
+            out.push_str(&text);
+            prev_end = end;
+        }
+        out.push_str(&self.source[prev_end..]);
+
+        Ok(out)
+    }
+}
+
+/// Return the source of a node as if it were at the top level of the document.
+///
+/// A node in a container, such as a block quote or a list item, has the container prefix, such
+/// as `> ` or indentation, on every line but the first. The prefix is removed so that the
+/// returned source parses to the same node.
+pub(crate) fn node_source(source: &str, position: &Position) -> String {
+    let start = position.start.offset;
+    let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let prefix = &source[line_start..start];
+
+    let mut lines = source[start..position.end.offset].split('\n');
+    let mut out = lines.next().unwrap_or_default().to_string();
+
+    for line in lines {
+        out.push('\n');
+
+        if let Some(stripped) = line.strip_prefix(prefix) {
+            out.push_str(stripped);
+        } else {
+            // A list item prefix such as "- " is indentation on the following lines.
+            let indent = line
+                .chars()
+                .take(prefix.chars().count())
+                .take_while(|c| *c == ' ')
+                .count();
+            out.push_str(&line[indent..]);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use markdown::unist::Point;
+
+    use super::*;
+
+    fn pos(start: usize, end: usize) -> Position {
+        Position {
+            start: Point::new(1, start + 1, start),
+            end: Point::new(1, end + 1, end),
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut s = Splice::new("a $x$ b $y$");
+        s.replace(&pos(8, 11), "Y");
+        s.replace(&pos(2, 5), "X");
+
+        assert_eq!(s.apply().unwrap(), "a X b Y");
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let mut s = Splice::new("abcdef");
+        s.replace(&pos(0, 3), "");
+        s.replace(&pos(2, 4), "");
+
+        assert!(s.apply().is_err());
+    }
+
+    #[test]
+    fn test_node_source() {
+        let md = "> | a |\n> | - |\n> | 1 |\nafter";
+        assert_eq!(node_source(md, &pos(2, 23)), "| a |\n| - |\n| 1 |");
+
+        let md = "- ```\n  x\n  ```\n";
+        assert_eq!(node_source(md, &pos(2, 15)), "```\nx\n```");
+    }
+}
@@ -0,0 +1,338 @@
+use markdown::mdast::Html;
+use markdown::mdast::Node;
+
+use crate::md::escape_html;
+use crate::md::parse;
+use crate::md::transform::async_trait;
+use crate::md::transform::escape_raw_html;
+use crate::md::transform::Transform;
+use crate::md::walk_mut;
+
+/// The kinds of GitHub alerts, which decide the color of a callout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl AlertKind {
+    /// The kind of a GitHub alert marker, such as "NOTE" in `> [!NOTE]`, in any case.
+    pub fn from_github(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "note" => Some(AlertKind::Note),
+            "tip" => Some(AlertKind::Tip),
+            "important" => Some(AlertKind::Important),
+            "warning" => Some(AlertKind::Warning),
+            "caution" => Some(AlertKind::Caution),
+            _ => None,
+        }
+    }
+
+    /// The kind closest to a MkDocs admonition type, such as "danger" in `!!! danger`.
+    pub fn from_mkdocs(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "note" | "abstract" | "summary" | "tldr" | "info" | "todo" | "example" | "quote"
+            | "cite" => Some(AlertKind::Note),
+            "tip" | "hint" | "success" | "check" | "done" | "question" | "help" | "faq" => {
+                Some(AlertKind::Tip)
+            }
+            "important" => Some(AlertKind::Important),
+            "warning" | "caution" | "attention" => Some(AlertKind::Warning),
+            "danger" | "error" | "failure" | "fail" | "missing" | "bug" => Some(AlertKind::Caution),
+            _ => None,
+        }
+    }
+
+    /// The name used in the css class `markdown-alert-{name}`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertKind::Note => "note",
+            AlertKind::Tip => "tip",
+            AlertKind::Important => "important",
+            AlertKind::Warning => "warning",
+            AlertKind::Caution => "caution",
+        }
+    }
+
+    /// The title GitHub shows.
+    pub fn title(&self) -> &'static str {
+        match self {
+            AlertKind::Note => "Note",
+            AlertKind::Tip => "Tip",
+            AlertKind::Important => "Important",
+            AlertKind::Warning => "Warning",
+            AlertKind::Caution => "Caution",
+        }
+    }
+}
+
+/// Convert GitHub alerts and MkDocs admonitions to callouts styled as GitHub does:
+///
+/// ```markdown
+/// > [!NOTE]
+/// > Read this.
+///
+/// !!! danger "Do not"
+///
+///     Do that.
+/// ```
+///
+/// A callout is a `<div class="markdown-alert markdown-alert-{kind}">` with a title, which
+/// `github-markdown.css` styles.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertAlerts {
+    /// Keep raw html in the body of an admonition, which is parsed from its indented block.
+    /// Set it as the [`Pipeline`] it runs in does.
+    ///
+    /// [`Pipeline`]: crate::md::transform::Pipeline
+    pub allow_dangerous_html: bool,
+}
+
+impl ConvertAlerts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dangerous_html(mut self, allow: bool) -> Self {
+        self.allow_dangerous_html = allow;
+        self
+    }
+}
+
+#[async_trait]
+impl Transform for ConvertAlerts {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        if let Some(children) = root.children_mut() {
+            convert(children, self.allow_dangerous_html)?;
+        }
+        Ok(())
+    }
+}
+
+fn convert(children: &mut Vec<Node>, allow_dangerous_html: bool) -> anyhow::Result<()> {
+    let mut nodes = std::mem::take(children).into_iter().peekable();
+
+    while let Some(mut node) = nodes.next() {
+        if let Some(kind) = github_alert(&node) {
+            let Node::Blockquote(quote) = node else {
+                unreachable!("an alert is a block quote");
+            };
+            let mut body = quote.children;
+            strip_first_line(&mut body);
+            convert(&mut body, allow_dangerous_html)?;
+            push_callout(children, kind, Some(kind.title()), body);
+            continue;
+        }
+
+        if let Some((kind, title)) = admonition(&node) {
+            // The lines right after the marker are in its paragraph, the indented blocks
+            // after a blank line are a code block.
+            let mut body = vec![node];
+            strip_first_line(&mut body);
+            convert(&mut body, allow_dangerous_html)?;
+
+            if let Some(Node::Code(code)) = nodes.next_if(is_admonition_body) {
+                let mut root = parse(&code.value)?;
+                if !allow_dangerous_html {
+                    escape_raw_html(&mut root);
+                }
+                let mut blocks = root.children_mut().map(std::mem::take).unwrap_or_default();
+                // Nested admonitions are told from fenced code by their positions, which are
+                // then cleared since they are relative to the code block, not the document.
+                convert(&mut blocks, allow_dangerous_html)?;
+                for block in &mut blocks {
+                    walk_mut(block, &mut |n| n.position_set(None));
+                }
+                body.extend(blocks);
+            }
+
+            let title = title.as_deref().unwrap_or(kind.title());
+            push_callout(children, kind, Some(title).filter(|t| !t.is_empty()), body);
+            continue;
+        }
+
+        if let Some(c) = node.children_mut() {
+            convert(c, allow_dangerous_html)?;
+        }
+        children.push(node);
+    }
+
+    Ok(())
+}
+
+/// The kind of a block quote whose first line is a GitHub alert marker, such as `[!NOTE]`.
+pub(crate) fn github_alert(node: &Node) -> Option<AlertKind> {
+    let Node::Blockquote(quote) = node else {
+        return None;
+    };
+    let marker = first_line(quote.children.first()?)?;
+    AlertKind::from_github(marker.trim_end().strip_prefix("[!")?.strip_suffix(']')?)
+}
+
+/// The kind and title of a paragraph whose first line is a MkDocs admonition marker, such as
+/// `!!! note "Title"`, or `??? note` for a collapsible one. The title is `None` if not given.
+pub(crate) fn admonition(node: &Node) -> Option<(AlertKind, Option<String>)> {
+    let marker = first_line(node)?;
+    let rest = ["???+", "???", "!!!"]
+        .iter()
+        .find_map(|m| marker.strip_prefix(m))?;
+    if !rest.starts_with(' ') {
+        return None;
+    }
+
+    let rest = rest.trim();
+    let (kind, title) = match rest.split_once(' ') {
+        Some((kind, title)) => {
+            let title = title.trim();
+            let title = title.strip_prefix('"')?.strip_suffix('"')?;
+            (kind, Some(title.to_string()))
+        }
+        None => (rest, None),
+    };
+
+    Some((AlertKind::from_mkdocs(kind)?, title))
+}
+
+/// Whether a node is the indented body after an admonition marker: an indented code block,
+/// not a fenced one without a language.
+///
+/// An indented code block spans exactly the lines of its value, while a fenced one has a line
+/// more for each fence.
+pub(crate) fn is_admonition_body(node: &Node) -> bool {
+    let Node::Code(code) = node else {
+        return false;
+    };
+    let Some(position) = &code.position else {
+        return false;
+    };
+    code.lang.is_none() && position.end.line - position.start.line + 1 == code.value.lines().count()
+}
+
+/// The first line of a paragraph, if it starts with text.
+fn first_line(node: &Node) -> Option<&str> {
+    let Node::Paragraph(p) = node else {
+        return None;
+    };
+    let Some(Node::Text(text)) = p.children.first() else {
+        return None;
+    };
+    text.value.split('\n').next()
+}
+
+/// Remove the first line of the first paragraph in `blocks`, and the paragraph if nothing is
+/// left of it.
+fn strip_first_line(blocks: &mut Vec<Node>) {
+    let Some(Node::Paragraph(p)) = blocks.first_mut() else {
+        return;
+    };
+
+    if let Some(Node::Text(text)) = p.children.first_mut() {
+        match text.value.split_once('\n') {
+            Some((_, rest)) => {
+                text.value = rest.to_string();
+                text.position = None;
+            }
+            None => {
+                p.children.remove(0);
+            }
+        }
+        p.position = None;
+    }
+
+    if p.children.is_empty() {
+        blocks.remove(0);
+    }
+}
+
+fn push_callout(children: &mut Vec<Node>, kind: AlertKind, title: Option<&str>, body: Vec<Node>) {
+    let mut open = format!(
+        r#"<div class="markdown-alert markdown-alert-{}">"#,
+        kind.name()
+    );
+    if let Some(title) = title {
+        open.push_str(&format!(
+            "\n<p class=\"markdown-alert-title\">{}</p>",
+            escape_html(title)
+        ));
+    }
+
+    // Html nodes are written as they are, without a line break after a block.
+    children.push(html(open + "\n"));
+    children.extend(body);
+    children.push(html("</div>\n"));
+}
+
+fn html(value: impl Into<String>) -> Node {
+    Node::Html(Html {
+        value: value.into(),
+        position: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::transform::Pipeline;
+
+    async fn run(md: &str) -> String {
+        Pipeline::new()
+            .with(ConvertAlerts::new())
+            .run(md)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_github_alerts() {
+        assert_eq!(
+            run("> [!NOTE]\n> Read *this*.\n>\n> > [!caution]\n> > No.\n").await,
+            "<div class=\"markdown-alert markdown-alert-note\">\n<p class=\"markdown-alert-title\">Note</p>\n<p>Read <em>this</em>.</p>\n<div class=\"markdown-alert markdown-alert-caution\">\n<p class=\"markdown-alert-title\">Caution</p>\n<p>No.</p>\n</div>\n</div>\n"
+        );
+
+        // Not a marker alone on the first line.
+        assert_eq!(
+            run("> [!TIP] more\n\n> [!FOO]\n> x\n").await,
+            "<blockquote>\n<p>[!TIP] more</p>\n</blockquote>\n<blockquote>\n<p>[!FOO]\nx</p>\n</blockquote>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mkdocs_admonitions() {
+        assert_eq!(
+            run("!!! danger \"Don't & do\"\n\n    Do *that*.\n\n    ```\n    code\n    ```\n\n!!! tip\n    Lazy.\n\n??? note \"\"\n\n    x\n").await,
+            "<div class=\"markdown-alert markdown-alert-caution\">\n<p class=\"markdown-alert-title\">Don't &amp; do</p>\n<p>Do <em>that</em>.</p>\n<pre><code>code\n</code></pre>\n</div>\n<div class=\"markdown-alert markdown-alert-tip\">\n<p class=\"markdown-alert-title\">Tip</p>\n<p>Lazy.</p>\n</div>\n<div class=\"markdown-alert markdown-alert-note\">\n<p>x</p>\n</div>\n"
+        );
+
+        assert_eq!(
+            run("!!! unknown\n\n!!!note\n").await,
+            "<p>!!! unknown</p>\n<p>!!!note</p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mkdocs_admonition_fenced_code() {
+        // A fence after the marker is code after an empty callout, not its body.
+        assert_eq!(
+            run("!!! note\n\n```\nplain *fenced*\n```\n\n!!! tip\n\n```\n```\n").await,
+            "<div class=\"markdown-alert markdown-alert-note\">\n<p class=\"markdown-alert-title\">Note</p>\n</div>\n<pre><code>plain *fenced*\n</code></pre>\n<div class=\"markdown-alert markdown-alert-tip\">\n<p class=\"markdown-alert-title\">Tip</p>\n</div>\n<pre><code></code></pre>\n"
+        );
+
+        // Nested admonitions in the body, and its raw html as the pipeline allows.
+        let md = "!!! note\n\n    !!! tip\n\n        <b>x</b>\n";
+        let want = |b: &str| {
+            format!("<div class=\"markdown-alert markdown-alert-note\">\n<p class=\"markdown-alert-title\">Note</p>\n<div class=\"markdown-alert markdown-alert-tip\">\n<p class=\"markdown-alert-title\">Tip</p>\n{}</div>\n</div>\n", b)
+        };
+        assert_eq!(run(md).await, want("<p>&lt;b&gt;x&lt;/b&gt;</p>\n"));
+
+        let html = Pipeline::new()
+            .with_dangerous_html(true)
+            .with(ConvertAlerts::new().with_dangerous_html(true))
+            .run(md)
+            .await
+            .unwrap();
+        assert_eq!(html, want("<p><b>x</b></p>\n"));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use markdown::mdast::Html;
+use markdown::mdast::Node;
+
+use crate::export::assets::is_local;
+use crate::export::assets::resolve_local;
+use crate::md::data_url;
+use crate::md::escape_html;
+use crate::md::transform::Transform;
+use crate::md::walk;
+use crate::md::walk_mut;
+use crate::Mime;
+
+/// Embed local images as `data:` urls, so that the html shows them wherever it is pasted.
+///
+/// Images and image references whose file is not found, or is not an image, are kept as they
+/// are.
+pub struct EmbedImages {
+    /// The directory to resolve relative urls against.
+    pub base_dir: PathBuf,
+
+    /// Urls starting with a prefix are resolved against its directory instead, such as
+    /// `("assets/", "out/assets")`.
+    pub prefixes: Vec<(String, PathBuf)>,
+}
+
+impl EmbedImages {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            prefixes: vec![],
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        self.prefixes.push((prefix.into(), dir.into()));
+        self
+    }
+
+    /// Build an `<img>` with the image at `url` embedded, or `None` if it can not be read.
+    fn img(&self, url: &str, alt: &str, title: Option<&str>) -> Option<String> {
+        if !is_local(url) {
+            return None;
+        }
+
+        let path = self.resolve(url);
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if !Mime::get(&ext).is_some_and(|m| m.starts_with("image/")) {
+            return None;
+        }
+        let data = fs::read(&path).ok()?;
+
+        let title = match title {
+            Some(title) => format!(" title=\"{}\"", escape_html(title)),
+            None => String::new(),
+        };
+        Some(format!(
+            "<img src=\"{}\" alt=\"{}\"{} />",
+            data_url(&ext, &data),
+            escape_html(alt),
+            title
+        ))
+    }
+
+    fn resolve(&self, url: &str) -> PathBuf {
+        for (prefix, dir) in &self.prefixes {
+            if let Some(rest) = url.strip_prefix(prefix.as_str()) {
+                return resolve_local(dir, rest);
+            }
+        }
+        resolve_local(&self.base_dir, url)
+    }
+}
+
+#[async_trait]
+impl Transform for EmbedImages {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        // Image references, such as `![a][logo]`, take the url of their definition.
+        let mut definitions = HashMap::new();
+        walk(root, &mut |node| {
+            if let Node::Definition(def) = node {
+                definitions.insert(def.identifier.clone(), (def.url.clone(), def.title.clone()));
+            }
+        });
+
+        walk_mut(root, &mut |node| {
+            let (img, position) = match node {
+                Node::Image(image) => (
+                    self.img(&image.url, &image.alt, image.title.as_deref()),
+                    &mut image.position,
+                ),
+                Node::ImageReference(image) => {
+                    let Some((url, title)) = definitions.get(&image.identifier) else {
+                        return;
+                    };
+                    (
+                        self.img(url, &image.alt, title.as_deref()),
+                        &mut image.position,
+                    )
+                }
+                _ => return,
+            };
+
+            if let Some(img) = img {
+                *node = Node::Html(Html {
+                    value: img,
+                    position: position.take(),
+                });
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::transform::Pipeline;
+
+    #[tokio::test]
+    async fn test_embed_images() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("out/assets")).unwrap();
+        fs::write(dir.path().join("out/assets/a.png"), "A").unwrap();
+        fs::write(dir.path().join("b.png"), "B").unwrap();
+
+        let embed =
+            EmbedImages::new(dir.path()).with_prefix("assets/", dir.path().join("out/assets"));
+        let md = "![a](assets/a.png) ![b][b] ![c](missing.png) ![d](https://x.com/d.png)\n\n[b]: b.png \"B\"\n";
+        let html = Pipeline::new().with(embed).run(md).await.unwrap();
+
+        assert_eq!(
+            html,
+            "<p><img src=\"data:image/png;base64,QQ==\" alt=\"a\" /> <img src=\"data:image/png;base64,Qg==\" alt=\"b\" title=\"B\" /> <img src=\"missing.png\" alt=\"c\" /> <img src=\"https://x.com/d.png\" alt=\"d\" /></p>\n"
+        );
+    }
+}
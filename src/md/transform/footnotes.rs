@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use markdown::mdast::InlineCode;
+use markdown::mdast::Node;
+use markdown::mdast::Paragraph;
+use markdown::mdast::Text;
+use markdown::mdast::ThematicBreak;
+
+use crate::md::transform::async_trait;
+use crate::md::transform::Transform;
+use crate::md::walk;
+
+/// How footnotes are written, for targets that do not support links within a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FootnoteStyle {
+    /// Keep GFM footnotes: `<sup>` links and a footnote section.
+    #[default]
+    Keep,
+
+    /// Replace a reference with `[n]` and list the notes as plain text at the end.
+    EndNotes,
+
+    /// Put the note in parentheses where it is referenced.
+    Inline,
+
+    /// Same as [`Self::EndNotes`], and also replace external links with their text and `[n]`,
+    /// listing the urls with the notes.
+    LinkReferences,
+}
+
+impl FromStr for FootnoteStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(FootnoteStyle::Keep),
+            "end-notes" => Ok(FootnoteStyle::EndNotes),
+            "inline" => Ok(FootnoteStyle::Inline),
+            "link-references" => Ok(FootnoteStyle::LinkReferences),
+            _ => anyhow::bail!(
+                "Unknown footnote style: {}. Supported: keep, end-notes, inline, link-references",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for FootnoteStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FootnoteStyle::Keep => write!(f, "keep"),
+            FootnoteStyle::EndNotes => write!(f, "end-notes"),
+            FootnoteStyle::Inline => write!(f, "inline"),
+            FootnoteStyle::LinkReferences => write!(f, "link-references"),
+        }
+    }
+}
+
+/// Convert footnotes, and external links with [`FootnoteStyle::LinkReferences`], to plain
+/// text. A reference link such as `[text][ref]` is an external link if its definition is.
+///
+/// Notes are numbered in the order they are first referenced, and the content of a note is
+/// converted too, so that a footnote referenced only from another one is listed after it; a
+/// url linked twice gets one number. Definitions that are never referenced are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertFootnotes {
+    pub style: FootnoteStyle,
+}
+
+impl ConvertFootnotes {
+    pub fn new(style: FootnoteStyle) -> Self {
+        Self { style }
+    }
+}
+
+/// An entry of the list at the end of the document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Note {
+    Footnote(String),
+    Link(String),
+}
+
+/// The definitions of a document, by identifier.
+#[derive(Debug, Default)]
+struct Definitions {
+    /// The content of footnotes, taken out of the tree.
+    footnotes: HashMap<String, Vec<Node>>,
+
+    /// The urls of links, for reference links.
+    links: HashMap<String, String>,
+}
+
+#[async_trait]
+impl Transform for ConvertFootnotes {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        if self.style == FootnoteStyle::Keep {
+            return Ok(());
+        }
+
+        let Some(children) = root.children_mut() else {
+            return Ok(());
+        };
+
+        let mut definitions = Definitions::default();
+        for child in children.iter() {
+            walk(child, &mut |node| {
+                if let Node::Definition(def) = node {
+                    definitions
+                        .links
+                        .entry(def.identifier.clone())
+                        .or_insert_with(|| def.url.clone());
+                }
+            });
+        }
+        take_definitions(children, &mut definitions.footnotes);
+
+        let mut notes = vec![];
+        self.convert(children, &definitions, &mut notes, &mut vec![]);
+
+        // Converting a note may number more, which are converted in turn.
+        let mut entries = vec![];
+        let mut i = 0;
+        while i < notes.len() {
+            let number = text(format!("[{}] ", i + 1));
+            match notes[i].clone() {
+                Note::Footnote(id) => {
+                    let mut content = definitions.footnotes[&id].clone();
+                    self.convert(&mut content, &definitions, &mut notes, &mut vec![]);
+                    match content.first_mut() {
+                        Some(Node::Paragraph(p)) => p.children.insert(0, number),
+                        _ => content.insert(0, paragraph(vec![number])),
+                    }
+                    entries.extend(content);
+                }
+                Note::Link(url) => {
+                    let url = Node::InlineCode(InlineCode {
+                        value: url,
+                        position: None,
+                    });
+                    entries.push(paragraph(vec![number, url]));
+                }
+            }
+            i += 1;
+        }
+
+        if !entries.is_empty() {
+            children.push(Node::ThematicBreak(ThematicBreak { position: None }));
+            children.extend(entries);
+        }
+        Ok(())
+    }
+}
+
+impl ConvertFootnotes {
+    /// Convert the references in `children`. `inlined` are the footnotes being put inline,
+    /// whose references in themselves are dropped rather than expanded forever.
+    fn convert(
+        &self,
+        children: &mut Vec<Node>,
+        definitions: &Definitions,
+        notes: &mut Vec<Note>,
+        inlined: &mut Vec<String>,
+    ) {
+        for mut node in std::mem::take(children) {
+            let url = match &node {
+                Node::FootnoteReference(r) if definitions.footnotes.contains_key(&r.identifier) => {
+                    if self.style == FootnoteStyle::Inline {
+                        if !inlined.contains(&r.identifier) {
+                            let mut content = inline_content(&definitions.footnotes[&r.identifier]);
+                            inlined.push(r.identifier.clone());
+                            self.convert(&mut content, definitions, notes, inlined);
+                            inlined.pop();
+
+                            children.push(text(" ("));
+                            children.extend(content);
+                            children.push(text(")"));
+                        }
+                    } else {
+                        let n = number(notes, Note::Footnote(r.identifier.clone()));
+                        children.push(text(format!("[{}]", n)));
+                    }
+                    continue;
+                }
+                Node::Link(link) => Some(link.url.clone()),
+                Node::LinkReference(r) => definitions.links.get(&r.identifier).cloned(),
+                _ => None,
+            };
+
+            match url {
+                Some(url) if self.style == FootnoteStyle::LinkReferences && is_external(&url) => {
+                    // An autolink has only the url as text, which is listed at the end.
+                    let autolink = node.to_string() == url;
+                    let mut content = node.children_mut().map(std::mem::take).unwrap_or_default();
+                    self.convert(&mut content, definitions, notes, inlined);
+
+                    let n = number(notes, Note::Link(url));
+                    if !autolink {
+                        children.extend(content);
+                    }
+                    children.push(text(format!("[{}]", n)));
+                }
+                _ => {
+                    if let Some(c) = node.children_mut() {
+                        self.convert(c, definitions, notes, inlined);
+                    }
+                    children.push(node);
+                }
+            }
+        }
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Remove footnote definitions from the tree and collect their content by identifier.
+fn take_definitions(children: &mut Vec<Node>, definitions: &mut HashMap<String, Vec<Node>>) {
+    for mut node in std::mem::take(children) {
+        if let Node::FootnoteDefinition(def) = node {
+            definitions.insert(def.identifier, def.children);
+            continue;
+        }
+        if let Some(c) = node.children_mut() {
+            take_definitions(c, definitions);
+        }
+        children.push(node);
+    }
+}
+
+/// The 1-based number of a note, adding it to the list if it is new.
+fn number(notes: &mut Vec<Note>, note: Note) -> usize {
+    match notes.iter().position(|n| *n == note) {
+        Some(i) => i + 1,
+        None => {
+            notes.push(note);
+            notes.len()
+        }
+    }
+}
+
+/// The inline content of the blocks of a note, with paragraphs joined by a space.
+fn inline_content(blocks: &[Node]) -> Vec<Node> {
+    let mut inlines = vec![];
+    for block in blocks {
+        if !inlines.is_empty() {
+            inlines.push(text(" "));
+        }
+        match block {
+            Node::Paragraph(p) => inlines.extend(p.children.iter().cloned()),
+            _ => inlines.push(text(block.to_string())),
+        }
+    }
+    inlines
+}
+
+fn text(value: impl Into<String>) -> Node {
+    Node::Text(Text {
+        value: value.into(),
+        position: None,
+    })
+}
+
+fn paragraph(children: Vec<Node>) -> Node {
+    Node::Paragraph(Paragraph {
+        children,
+        position: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::transform::Pipeline;
+
+    const MD: &str = "A[^x] and [b](https://b.com)[^y], [b](https://b.com) [c](c.md) A[^x].\n\n[^x]: Note *x*.\n\n    More.\n[^y]: Y.\n[^z]: Unused.\n";
+
+    async fn run(style: FootnoteStyle) -> String {
+        Pipeline::new()
+            .with(ConvertFootnotes::new(style))
+            .run(MD)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_keep() {
+        let html = run(FootnoteStyle::Keep).await;
+        assert!(html.contains("<section data-footnotes"));
+    }
+
+    #[tokio::test]
+    async fn test_end_notes() {
+        assert_eq!(
+            run(FootnoteStyle::EndNotes).await,
+            "<p>A[1] and <a href=\"https://b.com\">b</a>[2], <a href=\"https://b.com\">b</a> <a href=\"c.md\">c</a> A[1].</p>\n<hr />\n<p>[1] Note <em>x</em>.</p>\n<p>More.</p>\n<p>[2] Y.</p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inline() {
+        assert_eq!(
+            run(FootnoteStyle::Inline).await,
+            "<p>A (Note <em>x</em>. More.) and <a href=\"https://b.com\">b</a> (Y.), <a href=\"https://b.com\">b</a> <a href=\"c.md\">c</a> A (Note <em>x</em>. More.).</p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_link_references() {
+        assert_eq!(
+            run(FootnoteStyle::LinkReferences).await,
+            "<p>A[1] and b[2][3], b[2] <a href=\"c.md\">c</a> A[1].</p>\n<hr />\n<p>[1] Note <em>x</em>.</p>\n<p>More.</p>\n<p>[2] <code>https://b.com</code></p>\n<p>[3] Y.</p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reference_links() {
+        let md = "See [b][ref], [the docs] and [c][local].\n\n[ref]: https://b.com\n[the docs]: https://docs.rs\n[local]: c.md\n";
+        let html = Pipeline::new()
+            .with(ConvertFootnotes::new(FootnoteStyle::LinkReferences))
+            .run(md)
+            .await
+            .unwrap();
+        assert_eq!(
+            html,
+            "<p>See b[1], the docs[2] and <a href=\"c.md\">c</a>.</p>\n<hr />\n<p>[1] <code>https://b.com</code></p>\n<p>[2] <code>https://docs.rs</code></p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notes_in_notes() {
+        // A note referenced only from another, and a link in a note, are converted too.
+        let md = "A[^a].\n\n[^a]: See [b](https://b.com)[^b].\n[^b]: B[^a].\n";
+        let run = |style| async move {
+            Pipeline::new()
+                .with(ConvertFootnotes::new(style))
+                .run(md)
+                .await
+                .unwrap()
+        };
+
+        assert_eq!(
+            run(FootnoteStyle::LinkReferences).await,
+            "<p>A[1].</p>\n<hr />\n<p>[1] See b[2][3].</p>\n<p>[2] <code>https://b.com</code></p>\n<p>[3] B[1].</p>\n"
+        );
+        assert_eq!(
+            run(FootnoteStyle::EndNotes).await,
+            "<p>A[1].</p>\n<hr />\n<p>[1] See <a href=\"https://b.com\">b</a>[2].</p>\n<p>[2] B[1].</p>\n"
+        );
+        // A note in itself is not expanded again.
+        assert_eq!(
+            run(FootnoteStyle::Inline).await,
+            "<p>A (See <a href=\"https://b.com\">b</a> (B.).).</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_footnote_style_from_str() {
+        for style in [
+            FootnoteStyle::Keep,
+            FootnoteStyle::EndNotes,
+            FootnoteStyle::Inline,
+            FootnoteStyle::LinkReferences,
+        ] {
+            assert_eq!(style.to_string().parse::<FootnoteStyle>().unwrap(), style);
+        }
+        assert!("foo".parse::<FootnoteStyle>().is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use markdown::mdast::Html;
+use markdown::mdast::Node;
+
+use crate::check::heading_slugs;
+use crate::md::escape_html;
+use crate::md::transform::async_trait;
+use crate::md::transform::Transform;
+use crate::md::walk_mut;
+
+/// Shift the depth of every heading by `by`, within `#` to `######`.
+///
+/// E.g., shift by 1 to embed a document under the title of another page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShiftHeadings {
+    pub by: i8,
+}
+
+impl ShiftHeadings {
+    pub fn new(by: i8) -> Self {
+        Self { by }
+    }
+}
+
+#[async_trait]
+impl Transform for ShiftHeadings {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        walk_mut(root, &mut |node| {
+            if let Node::Heading(h) = node {
+                h.depth = (h.depth as i16 + self.by as i16).clamp(1, 6) as u8;
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Give every heading the anchor GitHub generates for it, such as `setup` for `## Setup`, so
+/// that links to `#setup` work. See [`heading_slugs`].
+///
+/// The anchor is an empty `<a id="...">` at the start of the heading, as GitHub writes it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeadingIds {
+    /// Put before every id, such as the id of a chapter to keep the ids of chapters in one
+    /// page apart.
+    pub prefix: String,
+}
+
+impl HeadingIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Transform for HeadingIds {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        let mut slugs = heading_slugs(root).into_iter();
+
+        walk_mut(root, &mut |node| {
+            let Node::Heading(h) = node else {
+                return;
+            };
+            let Some(slug) = slugs.next() else {
+                return;
+            };
+            let id = escape_html(&format!("{}{}", self.prefix, slug));
+            h.children.insert(
+                0,
+                Node::Html(Html {
+                    value: format!("<a id=\"{}\" class=\"anchor\" href=\"#{}\"></a>", id, id),
+                    position: None,
+                }),
+            );
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::transform::Pipeline;
+
+    #[tokio::test]
+    async fn test_shift_headings() {
+        let md = "# a\n\n###### b\n\n> ## c\n";
+
+        let html = Pipeline::new()
+            .with(ShiftHeadings::new(1))
+            .run(md)
+            .await
+            .unwrap();
+        assert_eq!(
+            html,
+            "<h2>a</h2>\n<h6>b</h6>\n<blockquote>\n<h3>c</h3>\n</blockquote>\n"
+        );
+
+        let html = Pipeline::new()
+            .with(ShiftHeadings::new(-2))
+            .run(md)
+            .await
+            .unwrap();
+        assert!(html.starts_with("<h1>a</h1>\n<h4>b</h4>\n"), "{}", html);
+    }
+
+    #[tokio::test]
+    async fn test_heading_ids() {
+        let html = Pipeline::new()
+            .with(HeadingIds::new().with_prefix("ch-"))
+            .run("# A *b*\n\n## A b\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            html,
+            "<h1><a id=\"ch-a-b\" class=\"anchor\" href=\"#ch-a-b\"></a>A <em>b</em></h1>\n<h2><a id=\"ch-a-b-1\" class=\"anchor\" href=\"#ch-a-b-1\"></a>A b</h2>\n"
+        );
+    }
+}
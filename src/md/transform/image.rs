@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use markdown::mdast::Html;
+use markdown::mdast::Node;
+use markdown::mdast::Root;
+
+use crate::md::data_url;
+use crate::md::escape_html;
+use crate::md::html::to_html;
+use crate::md::transform::async_trait;
+use crate::md::transform::is_inline;
+use crate::md::transform::replace_nodes;
+use crate::md::transform::select_nodes;
+use crate::md::transform::Transform;
+use crate::md::walk;
+use crate::render::github_markdown_page;
+use crate::render::with_chrome::WithChrome;
+
+/// Render the nodes `select` matches, such as tables, to images with the GitHub markdown
+/// style in headless chrome, and embed the images as `data:` urls.
+pub struct ReplaceWithImage {
+    select: Box<dyn Fn(&Node) -> bool + Send + Sync>,
+
+    /// Image type, such as "png" or "jpg".
+    pub format: String,
+
+    /// Directory to resolve relative urls in the rendered nodes against, such as images in a
+    /// table.
+    pub asset_base: Option<PathBuf>,
+}
+
+impl ReplaceWithImage {
+    pub fn new(select: impl Fn(&Node) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            select: Box::new(select),
+            format: "png".to_string(),
+            asset_base: None,
+        }
+    }
+
+    /// Replace tables.
+    pub fn tables() -> Self {
+        Self::new(|node| matches!(node, Node::Table(_)))
+    }
+
+    /// Replace code blocks in the language `lang`.
+    pub fn code(lang: impl Into<String>) -> Self {
+        let lang = lang.into();
+        Self::new(move |node| matches!(node, Node::Code(c) if c.lang.as_deref() == Some(&lang)))
+    }
+
+    pub fn with_format(mut self, format: impl Into<String>) -> Self {
+        self.format = format.into();
+        self
+    }
+
+    pub fn with_asset_base(mut self, asset_base: impl Into<PathBuf>) -> Self {
+        self.asset_base = Some(asset_base.into());
+        self
+    }
+
+    /// Render a node, with the definitions it may refer to, to an `<img>`.
+    async fn render(&self, node: &Node, definitions: &[Node]) -> anyhow::Result<Node> {
+        let mut children = vec![node.clone()];
+        children.extend_from_slice(definitions);
+        let html = to_html(&Node::Root(Root {
+            children,
+            position: None,
+        }));
+
+        let page = github_markdown_page(&html);
+        let data = WithChrome::render_markup(
+            "text/html",
+            &page,
+            &self.format,
+            None,
+            None,
+            self.asset_base.as_deref(),
+        )
+        .await?;
+
+        let img = format!(
+            r#"<img class="xpmd-image" alt="{}" src="{}" />"#,
+            escape_html(&node.to_string()),
+            data_url(&self.format, &data)
+        );
+        let value = if is_inline(node) {
+            img
+        } else {
+            format!("<p>{}</p>", img)
+        };
+
+        Ok(Node::Html(Html {
+            value,
+            position: node.position().cloned(),
+        }))
+    }
+}
+
+#[async_trait]
+impl Transform for ReplaceWithImage {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        let mut definitions = vec![];
+        walk(root, &mut |node| {
+            if let Node::Definition(_) = node {
+                definitions.push(node.clone());
+            }
+        });
+
+        let mut images = vec![];
+        for node in select_nodes(root, &self.select) {
+            images.push(self.render(node, &definitions).await?);
+        }
+
+        replace_nodes(root, &self.select, &mut images.into_iter());
+        Ok(())
+    }
+}
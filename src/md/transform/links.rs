@@ -0,0 +1,63 @@
+use markdown::mdast::Node;
+
+use crate::md::transform::async_trait;
+use crate::md::transform::Transform;
+use crate::md::walk_mut;
+
+type RewriteFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+/// Rewrite the urls of links, images and definitions.
+///
+/// The function returns the new url, or `None` to keep it.
+pub struct RewriteLinks {
+    rewrite: Box<RewriteFn>,
+}
+
+impl RewriteLinks {
+    pub fn new(rewrite: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            rewrite: Box::new(rewrite),
+        }
+    }
+}
+
+#[async_trait]
+impl Transform for RewriteLinks {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+        walk_mut(root, &mut |node| {
+            let url = match node {
+                Node::Link(link) => &mut link.url,
+                Node::Image(image) => &mut image.url,
+                Node::Definition(def) => &mut def.url,
+                _ => return,
+            };
+            if let Some(new_url) = (self.rewrite)(url) {
+                *url = new_url;
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md::transform::Pipeline;
+
+    #[tokio::test]
+    async fn test_rewrite_links() {
+        let rewrite =
+            RewriteLinks::new(|url| url.strip_suffix(".md").map(|stem| format!("{}.html", stem)));
+
+        let html = Pipeline::new()
+            .with(rewrite)
+            .run("[a](a.md) [b](https://b.com) [c][c] ![d](d.md)\n\n[c]: c.md\n")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            html,
+            "<p><a href=\"a.html\">a</a> <a href=\"https://b.com\">b</a> <a href=\"c.html\">c</a> <img src=\"d.html\" alt=\"d\" /></p>\n"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Passes over the mdast tree between parsing and serializing to html.
+//!
+//! A [`Pipeline`] parses markdown, runs its [`Transform`]s in order and serializes the tree
+//! with [`to_html`]. Built-in passes are in the submodules; implement [`Transform`] to add
+//! your own:
+//!
+//! ```
+//! use markdown::mdast::Node;
+//! use xp_md2html::md::transform::async_trait;
+//! use xp_md2html::md::transform::Pipeline;
+//! use xp_md2html::md::transform::ShiftHeadings;
+//! use xp_md2html::md::transform::Transform;
+//! use xp_md2html::md::walk_mut;
+//!
+//! struct Uppercase;
+//!
+//! #[async_trait]
+//! impl Transform for Uppercase {
+//!     async fn transform(&self, root: &mut Node) -> anyhow::Result<()> {
+//!         walk_mut(root, &mut |node| {
+//!             if let Node::Text(text) = node {
+//!                 text.value = text.value.to_uppercase();
+//!             }
+//!         });
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let pipeline = Pipeline::new().with(ShiftHeadings::new(1)).with(Uppercase);
+//!     let html = pipeline.run("# Hi\n").await.unwrap();
+//!     assert_eq!(html, "<h2>HI</h2>\n");
+//! }
+//! ```
+
+mod alerts;
+mod embed;
+mod footnotes;
+mod headings;
+mod image;
+mod links;
+
+pub(crate) use alerts::admonition;
+pub(crate) use alerts::github_alert;
+pub(crate) use alerts::is_admonition_body;
+pub use alerts::AlertKind;
+pub use alerts::ConvertAlerts;
+pub use async_trait::async_trait;
+pub use embed::EmbedImages;
+pub use footnotes::ConvertFootnotes;
+pub use footnotes::FootnoteStyle;
+pub use headings::HeadingIds;
+pub use headings::ShiftHeadings;
+pub use image::ReplaceWithImage;
+pub use links::RewriteLinks;
+use markdown::mdast::Node;
+use markdown::mdast::Text;
+
+use crate::md::html::to_html;
+use crate::md::parse;
+use crate::md::walk_mut;
+
+/// A pass that modifies the mdast tree.
+///
+/// Html to put in the output, such as a rendered formula, is added as a [`Node::Html`].
+#[async_trait]
+pub trait Transform: Send + Sync {
+    async fn transform(&self, root: &mut Node) -> anyhow::Result<()>;
+}
+
+/// Parse markdown, run transforms over the tree and serialize it to html.
+#[derive(Default)]
+pub struct Pipeline {
+    transforms: Vec<Box<dyn Transform>>,
+
+    /// Keep raw html in the markdown source. It is escaped by default, the same as the
+    /// markdown crate does.
+    pub allow_dangerous_html: bool,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a transform, which runs after the ones already added.
+    pub fn with(mut self, transform: impl Transform + 'static) -> Self {
+        self.push(transform);
+        self
+    }
+
+    pub fn push(&mut self, transform: impl Transform + 'static) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    pub fn push_boxed(&mut self, transform: Box<dyn Transform>) {
+        self.transforms.push(transform);
+    }
+
+    pub fn with_dangerous_html(mut self, allow: bool) -> Self {
+        self.allow_dangerous_html = allow;
+        self
+    }
+
+    /// Parse markdown source into a tree with every transform applied.
+    pub async fn parse(&self, md: &str) -> anyhow::Result<Node> {
+        let mut root = parse(md)?;
+
+        if !self.allow_dangerous_html {
+            escape_raw_html(&mut root);
+        }
+
+        for transform in &self.transforms {
+            transform.transform(&mut root).await?;
+        }
+
+        Ok(root)
+    }
+
+    /// Convert markdown source to html.
+    pub async fn run(&self, md: &str) -> anyhow::Result<String> {
+        let root = self.parse(md).await?;
+        Ok(to_html(&root))
+    }
+}
+
+/// Turn raw html from the source into text, so that it is escaped in the output.
+pub(crate) fn escape_raw_html(root: &mut Node) {
+    walk_mut(root, &mut |node| {
+        if let Node::Html(html) = node {
+            *node = Node::Text(Text {
+                value: std::mem::take(&mut html.value),
+                position: html.position.take(),
+            });
+        }
+    });
+}
+
+/// Return the nodes `select` matches in document order. The descendants of a matched node
+/// are not visited.
+pub fn select_nodes<'a>(root: &'a Node, select: &impl Fn(&Node) -> bool) -> Vec<&'a Node> {
+    fn visit<'a>(node: &'a Node, select: &impl Fn(&Node) -> bool, out: &mut Vec<&'a Node>) {
+        if select(node) {
+            out.push(node);
+        } else if let Some(children) = node.children() {
+            for child in children {
+                visit(child, select, out);
+            }
+        }
+    }
+
+    let mut out = vec![];
+    visit(root, select, &mut out);
+    out
+}
+
+/// Replace the nodes `select` matches, in the order of [`select_nodes`], with the next node
+/// of `replacements`. A node is kept if `replacements` runs out.
+///
+/// This is the second half of an async transform: select the nodes, render them, then put
+/// the results in.
+pub fn replace_nodes(
+    root: &mut Node,
+    select: &impl Fn(&Node) -> bool,
+    replacements: &mut impl Iterator<Item = Node>,
+) {
+    if select(root) {
+        if let Some(replacement) = replacements.next() {
+            *root = replacement;
+        }
+    } else if let Some(children) = root.children_mut() {
+        for child in children {
+            replace_nodes(child, select, replacements);
+        }
+    }
+}
+
+/// Whether a node is inline content, such as text or a link, rather than a block.
+pub(crate) fn is_inline(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Text(_)
+            | Node::Emphasis(_)
+            | Node::Strong(_)
+            | Node::Delete(_)
+            | Node::InlineCode(_)
+            | Node::InlineMath(_)
+            | Node::Break(_)
+            | Node::Link(_)
+            | Node::Image(_)
+            | Node::LinkReference(_)
+            | Node::ImageReference(_)
+            | Node::FootnoteReference(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_escape_raw_html() {
+        let html = Pipeline::new()
+            .run("<div>x</div>\n\na <b>b</b>\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            html,
+            "&lt;div&gt;x&lt;/div&gt;\n<p>a &lt;b&gt;b&lt;/b&gt;</p>\n"
+        );
+
+        let html = Pipeline::new()
+            .with_dangerous_html(true)
+            .run("a <b>b</b>\n")
+            .await
+            .unwrap();
+        assert_eq!(html, "<p>a <b>b</b></p>\n");
+    }
+
+    #[test]
+    fn test_select_and_replace() {
+        let mut root = parse("`a` and `b`\n\n> `c`\n").unwrap();
+        let is_code = |node: &Node| matches!(node, Node::InlineCode(_));
+
+        let values: Vec<String> = select_nodes(&root, &is_code)
+            .iter()
+            .map(|node| node.to_string())
+            .collect();
+        assert_eq!(values, vec!["a", "b", "c"]);
+
+        let mut replacements = ["A", "B"].into_iter().map(|s| {
+            Node::Text(Text {
+                value: s.to_string(),
+                position: None,
+            })
+        });
+        replace_nodes(&mut root, &is_code, &mut replacements);
+
+        assert_eq!(
+            to_html(&root),
+            "<p>A and B</p>\n<blockquote>\n<p><code>c</code></p>\n</blockquote>\n"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Look up the mime type of a file by the extensions of its name.
+
+use std::path::Path;
+
+use super::Mime;
+use super::MARKUP_MIMES;
+
+/// The mime type of a file, as its name tells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMime {
+    /// The type of the content, such as `image/svg+xml` for `.svgz` and `application/x-tar` for
+    /// `.tar.gz`.
+    pub mime: &'static str,
+
+    /// How the content is compressed, such as "gzip" for `.svgz` and `.tar.gz`.
+    pub encoding: Option<&'static str>,
+
+    /// The markup type of the extension before the one that decides, if it differs, such as
+    /// `text/markdown` for `notes.md.txt`. A part that names another type, such as the `es`
+    /// of `about.es.md`, is more likely a language or a version than an extension.
+    pub ambiguous: Option<&'static str>,
+}
+
+/// Extensions of a compression, and the encoding they stand for.
+const ENCODINGS: &[(&str, &str)] = &[
+    ("gz", "gzip"),
+    ("bz2", "bzip2"),
+    ("xz", "xz"),
+    ("br", "br"),
+    ("zst", "zstd"),
+];
+
+/// Extensions of a compressed type in one, such as `.tgz` for `.tar.gz`.
+const COMPRESSED: &[(&str, &str, &str)] = &[
+    ("svgz", "svg", "gzip"),
+    ("tgz", "tar", "gzip"),
+    ("tbz2", "tar", "bzip2"),
+    ("txz", "tar", "xz"),
+];
+
+pub(super) fn from_path(path: &Path) -> Option<PathMime> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+
+    // A leading dot hides a file rather than starting an extension, as of `.bashrc`.
+    let mut exts = name
+        .trim_start_matches('.')
+        .split('.')
+        .skip(1)
+        .collect::<Vec<_>>();
+
+    let mut encoding = None;
+    let last = *exts.last()?;
+    if let Some((_, enc)) = ENCODINGS.iter().find(|(ext, _)| *ext == last) {
+        // `.svg.gz` is svg compressed with gzip; a plain `.gz` is a gzip file.
+        let inner = exts.len().checked_sub(2).map(|i| exts[i]);
+        if inner.is_some_and(|e| !e.is_empty() && Mime::get(e).is_some()) {
+            exts.pop();
+            encoding = Some(*enc);
+        }
+    } else if let Some((_, ext, enc)) = COMPRESSED.iter().find(|(ext, _, _)| *ext == last) {
+        *exts.last_mut()? = ext;
+        encoding = Some(*enc);
+    }
+
+    let (last, before) = exts.split_last()?;
+    // The empty extension of a name ending with a dot is `application/octet-stream`.
+    let mime = Mime::get(last).filter(|_| !last.is_empty())?;
+
+    let ambiguous = before
+        .last()
+        .filter(|ext| !ext.is_empty())
+        .and_then(|ext| Mime::get(ext))
+        .filter(|other| *other != mime && MARKUP_MIMES.contains(other));
+
+    Some(PathMime {
+        mime,
+        encoding,
+        ambiguous,
+    })
+}
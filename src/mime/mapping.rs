@@ -297,6 +297,7 @@ pub fn build_mime_types() -> BTreeMap<&'static str, &'static str> {
         ("gv"          , "text/vnd.graphviz"                                                          ),
         ("gxf"         , "application/gxf"                                                            ),
         ("gxt"         , "application/vnd.geonext"                                                    ),
+        ("gz"          , "application/gzip"                                                           ),
         ("h"           , "text/x-c"                                                                   ),
         ("h261"        , "video/h261"                                                                 ),
         ("h263"        , "video/h263"                                                                 ),
@@ -415,6 +416,7 @@ pub fn build_mime_types() -> BTreeMap<&'static str, &'static str> {
         ("maker"       , "application/vnd.framemaker"                                                 ),
         ("man"         , "text/troff"                                                                 ),
         ("mar"         , "application/octet-stream"                                                   ),
+        ("markdown"    , "text/markdown"                                                              ),
         ("mathml"      , "application/mathml+xml"                                                     ),
         ("mb"          , "application/mathematica"                                                    ),
         ("mbk"         , "application/vnd.mobius.mbk"                                                 ),
@@ -422,6 +424,7 @@ pub fn build_mime_types() -> BTreeMap<&'static str, &'static str> {
         ("mc1"         , "application/vnd.medcalcdata"                                                ),
         ("mcd"         , "application/vnd.mcd"                                                        ),
         ("mcurl"       , "text/vnd.curl.mcurl"                                                        ),
+        ("md"          , "text/markdown"                                                              ),
         ("mdb"         , "application/x-msaccess"                                                     ),
         ("mdi"         , "image/vnd.ms-modi"                                                          ),
         ("me"          , "text/troff"                                                                 ),
@@ -971,6 +974,7 @@ pub fn build_mime_types() -> BTreeMap<&'static str, &'static str> {
         ("zir"         , "application/vnd.zul"                                                        ),
         ("zirz"        , "application/vnd.zul"                                                        ),
         ("zmm"         , "application/vnd.handheld-entertainment+xml"                                 ),
+        ("zst"         , "application/zstd"                                                           ),
     ]
     )
 }
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::Mime;
+
+/// A parsed mime type, such as `image/svg+xml` or `text/html; charset=UTF-8`.
+///
+/// The type, subtype, suffix and parameter names are lowercased when parsed, since they are
+/// case-insensitive; so is the value of `charset`. Other parameter values are kept as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MimeType {
+    pub type_: String,
+
+    /// The subtype without the suffix, such as "svg" of `image/svg+xml`.
+    pub subtype: String,
+
+    /// The structured syntax suffix, such as "xml" of `image/svg+xml`.
+    pub suffix: Option<String>,
+
+    /// `(name, value)` parameters in the order given, with quotes removed from values.
+    pub params: Vec<(String, String)>,
+}
+
+impl MimeType {
+    /// Parse a mime type, or look up a file extension such as "svg" or "MD".
+    pub fn parse_or_ext(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        if s.contains('/') {
+            return s.parse();
+        }
+        match Mime::get(&s.to_lowercase()) {
+            Some(mime) if !s.is_empty() => mime.parse(),
+            _ => anyhow::bail!("Unknown mime type: {}", s),
+        }
+    }
+
+    /// The type without parameters, such as `image/svg+xml`.
+    pub fn essence(&self) -> String {
+        match &self.suffix {
+            Some(suffix) => format!("{}/{}+{}", self.type_, self.subtype, suffix),
+            None => format!("{}/{}", self.type_, self.subtype),
+        }
+    }
+
+    /// Whether this is the type `essence`, such as "text/html", in any case and with any
+    /// parameters.
+    pub fn is(&self, essence: &str) -> bool {
+        self.essence().eq_ignore_ascii_case(essence.trim())
+    }
+
+    /// Whether this is a html page: `text/html` or `application/xhtml+xml`.
+    pub fn is_html(&self) -> bool {
+        self.is("text/html") || self.is("application/xhtml+xml")
+    }
+
+    /// The value of a parameter, by name in any case.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `charset` parameter, lowercased, such as "utf-8".
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// The file extension of this type in the mime table, such as "svg" for `image/svg+xml`.
+    pub fn extension(&self) -> Option<&'static str> {
+        Mime::get_suffix(&self.essence())
+    }
+}
+
+impl FromStr for MimeType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(';');
+        let essence = parts.next().unwrap_or_default().trim().to_lowercase();
+
+        let Some((type_, subtype)) = essence.split_once('/') else {
+            anyhow::bail!("Invalid mime type: {}", s);
+        };
+        if !is_token(type_) || !is_token(subtype) {
+            anyhow::bail!("Invalid mime type: {}", s);
+        }
+        let (subtype, suffix) = match subtype.rsplit_once('+') {
+            Some((subtype, suffix)) if !subtype.is_empty() && !suffix.is_empty() => {
+                (subtype, Some(suffix.to_string()))
+            }
+            _ => (subtype, None),
+        };
+
+        let mut params = vec![];
+        for param in parts.map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((name, value)) = param.split_once('=') else {
+                anyhow::bail!("Invalid parameter of mime type: {}", param);
+            };
+            let name = name.trim().to_lowercase();
+            if !is_token(&name) {
+                anyhow::bail!("Invalid parameter of mime type: {}", param);
+            }
+
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            let value = if name == "charset" {
+                value.to_lowercase()
+            } else {
+                value.to_string()
+            };
+            params.push((name, value));
+        }
+
+        Ok(Self {
+            type_: type_.to_string(),
+            subtype: subtype.to_string(),
+            suffix,
+            params,
+        })
+    }
+}
+
+impl fmt::Display for MimeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.essence())?;
+        for (name, value) in &self.params {
+            if is_token(value) {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                write!(f, "; {}=\"{}\"", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `s` is a non-empty token, as the parts of a mime type are.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mime: MimeType = "Image/SVG+XML; Charset=\"UTF-8\"; name=A b"
+            .parse()
+            .unwrap();
+        assert_eq!(mime.type_, "image");
+        assert_eq!(mime.subtype, "svg");
+        assert_eq!(mime.suffix.as_deref(), Some("xml"));
+        assert_eq!(mime.charset(), Some("utf-8"));
+        assert_eq!(mime.param("NAME"), Some("A b"));
+        assert_eq!(
+            mime.to_string(),
+            "image/svg+xml; charset=utf-8; name=\"A b\""
+        );
+        assert_eq!(mime.extension(), Some("svg"));
+
+        assert!(mime.is("image/svg+xml"));
+        assert!(!mime.is("image/svg"));
+        assert!(!"application/htmlx".parse::<MimeType>().unwrap().is_html());
+        assert!("TEXT/HTML;charset=utf-8"
+            .parse::<MimeType>()
+            .unwrap()
+            .is_html());
+
+        assert_eq!(
+            "text/html".parse::<MimeType>().unwrap(),
+            "TEXT/Html".parse::<MimeType>().unwrap()
+        );
+
+        for bad in ["html", "text/", "/html", "text/ht ml", "text/html; charset"] {
+            assert!(bad.parse::<MimeType>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_or_ext() {
+        assert_eq!(
+            MimeType::parse_or_ext("SVG").unwrap().essence(),
+            "image/svg+xml"
+        );
+        assert_eq!(
+            MimeType::parse_or_ext("md").unwrap().essence(),
+            "text/markdown"
+        );
+        assert_eq!(
+            MimeType::parse_or_ext("text/html").unwrap().essence(),
+            "text/html"
+        );
+        assert!(MimeType::parse_or_ext("nope").is_err());
+        assert!(MimeType::parse_or_ext("").is_err());
+    }
+}
@@ -1,9 +1,26 @@
+mod from_path;
 mod mapping;
+mod mime_type;
 mod reversed;
+mod sniff;
 
 use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::LazyLock;
 
+pub use from_path::PathMime;
+pub use mime_type::MimeType;
+
+/// The mime types of markup that Chrome renders, with markdown that is converted to html.
+pub(crate) const MARKUP_MIMES: &[&str] = &[
+    "text/html",
+    "application/xhtml+xml",
+    "image/svg+xml",
+    "application/xml",
+    "text/xml",
+    "text/markdown",
+];
+
 static TABLE: LazyLock<BTreeMap<&'static str, &'static str>> =
     LazyLock::new(mapping::build_mime_types);
 
@@ -19,7 +36,7 @@ fn build_reverse_mime_types() -> BTreeMap<&'static str, &'static str> {
 pub struct Mime;
 
 impl Mime {
-    pub fn get(ext: &str) -> Option<&str> {
+    pub fn get(ext: &str) -> Option<&'static str> {
         TABLE.get(ext).copied()
     }
 
@@ -28,9 +45,29 @@ impl Mime {
     }
 
     /// look up suffix by mime type
-    pub fn get_suffix(mime: &str) -> Option<&str> {
+    pub fn get_suffix(mime: &str) -> Option<&'static str> {
         REVERSE_TABLE.get(mime).copied()
     }
+
+    /// Look up the mime type of a file by its extension, in any case.
+    ///
+    /// A compression extension after another, as of `.tar.gz`, or in one, as of `.svgz`, gives
+    /// the type of the content and its encoding. If the extension before the last names a
+    /// different markup type, as of `notes.md.txt`, the last decides and the other is reported
+    /// in [`PathMime::ambiguous`]; other parts of a name, as of `about.es.md`, are not.
+    ///
+    /// Returns `None` if the file has no extension or an unknown one.
+    pub fn from_path(path: &Path) -> Option<PathMime> {
+        from_path::from_path(path)
+    }
+
+    /// Detect the mime type of content: png, jpeg, gif, webp, pdf and zip by their magic
+    /// bytes, and svg, html, xml, markdown or else plain text by what the text starts with.
+    ///
+    /// Returns `None` for empty content and binary content of other formats.
+    pub fn sniff(data: &[u8]) -> Option<&'static str> {
+        sniff::sniff(data)
+    }
 }
 
 #[cfg(test)]
@@ -134,6 +171,113 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_path() {
+        let from_path = |path: &str| Mime::from_path(Path::new(path));
+        let mime = |mime, encoding, ambiguous| {
+            Some(PathMime {
+                mime,
+                encoding,
+                ambiguous,
+            })
+        };
+
+        assert_eq!(from_path("docs/Page.HTML"), mime("text/html", None, None));
+        assert_eq!(from_path("a.md"), mime("text/markdown", None, None));
+        assert_eq!(
+            from_path("jquery.min.js"),
+            mime("application/javascript", None, None)
+        );
+
+        assert_eq!(
+            from_path("a.svgz"),
+            mime("image/svg+xml", Some("gzip"), None)
+        );
+        assert_eq!(
+            from_path("a.SVG.gz"),
+            mime("image/svg+xml", Some("gzip"), None)
+        );
+        assert_eq!(
+            from_path("a.tar.gz"),
+            mime("application/x-tar", Some("gzip"), None)
+        );
+        assert_eq!(
+            from_path("a.tgz"),
+            mime("application/x-tar", Some("gzip"), None)
+        );
+        assert_eq!(from_path("a.gz"), mime("application/gzip", None, None));
+        assert_eq!(from_path("v1.2.gz"), mime("application/gzip", None, None));
+
+        assert_eq!(
+            from_path("notes.md.txt"),
+            mime("text/plain", None, Some("text/markdown"))
+        );
+        assert_eq!(from_path("a.htm.html"), mime("text/html", None, None));
+        assert_eq!(
+            from_path("page.html.md"),
+            mime("text/markdown", None, Some("text/html"))
+        );
+        // Languages, versions and other parts of a name are not markup extensions.
+        assert_eq!(from_path("about.es.md"), mime("text/markdown", None, None));
+        assert_eq!(from_path("intro.book.html"), mime("text/html", None, None));
+        assert_eq!(from_path("a.c.md"), mime("text/markdown", None, None));
+        assert_eq!(from_path("notes.in.md"), mime("text/markdown", None, None));
+
+        assert_eq!(from_path("Makefile"), None);
+        assert_eq!(from_path(".bashrc"), None);
+        assert_eq!(from_path("a."), None);
+        assert_eq!(from_path("a.xyz123"), None);
+        assert_eq!(from_path("dir.html/"), mime("text/html", None, None));
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(Mime::sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(Mime::sniff(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+        assert_eq!(Mime::sniff(b"GIF89a"), Some("image/gif"));
+        assert_eq!(Mime::sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(Mime::sniff(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(Mime::sniff(b"PK\x03\x04"), Some("application/zip"));
+
+        assert_eq!(
+            Mime::sniff(b"\xef\xbb\xbf  <svg xmlns='http://www.w3.org/2000/svg'/>"),
+            Some("image/svg+xml")
+        );
+        assert_eq!(
+            Mime::sniff(b"<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n<svg/>"),
+            Some("image/svg+xml")
+        );
+        assert_eq!(
+            Mime::sniff(b"<?xml version=\"1.0\"?><a/>"),
+            Some("application/xml")
+        );
+        assert_eq!(Mime::sniff(b"<!DOCTYPE html><svg/>"), Some("text/html"));
+        assert_eq!(
+            Mime::sniff(b"<!-- logo -->\n<svg viewBox=\"0 0 1 1\"></svg>"),
+            Some("image/svg+xml")
+        );
+        // Only an svg root element is an svg, not one inside html.
+        assert_eq!(
+            Mime::sniff(b"<div><h1>x</h1><svg></svg></div>"),
+            Some("text/html")
+        );
+        assert_eq!(Mime::sniff(b"<svgx/>"), Some("text/html"));
+        assert_eq!(Mime::sniff(b"<p>x</p>"), Some("text/html"));
+
+        assert_eq!(Mime::sniff(b"# Title\n\ntext"), Some("text/markdown"));
+        assert_eq!(
+            Mime::sniff(b"<p align=\"center\">logo</p>\n\n## Usage\n"),
+            Some("text/markdown")
+        );
+        assert_eq!(Mime::sniff(b"- a\n- b\n"), Some("text/markdown"));
+        assert_eq!(Mime::sniff(b"- a\n"), Some("text/plain"));
+        assert_eq!(Mime::sniff("#hashtag 中文".as_bytes()), Some("text/plain"));
+
+        assert_eq!(Mime::sniff(b""), None);
+        assert_eq!(Mime::sniff(b"\0\x01\x02"), None);
+        assert_eq!(Mime::sniff(b"\xff\xfe\x00"), None);
+    }
+
     #[test]
     fn test_table_not_empty() {
         assert!(!TABLE.is_empty(), "MIME table should not be empty");
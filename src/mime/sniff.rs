@@ -0,0 +1,129 @@
+//! Detect the mime type of content from its leading bytes.
+
+/// Leading bytes of binary formats, and their mime types.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    // An empty zip, and a spanned one.
+    (b"PK\x05\x06", "application/zip"),
+    (b"PK\x07\x08", "application/zip"),
+];
+
+/// How many leading bytes of text are looked at.
+const HEAD_LEN: usize = 2048;
+
+pub(super) fn sniff(data: &[u8]) -> Option<&'static str> {
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return Some(mime);
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    sniff_text(data)
+}
+
+fn sniff_text(data: &[u8]) -> Option<&'static str> {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let head = &data[..data.len().min(HEAD_LEN)];
+
+    // The head may end in the middle of a character, but any other invalid byte is binary.
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    if text.contains('\0') {
+        return None;
+    }
+
+    let lower = text.trim_start().to_ascii_lowercase();
+    if lower.is_empty() {
+        return None;
+    }
+
+    if lower.starts_with('<') {
+        let mime = if lower.contains("<!doctype html") || lower.contains("<html") {
+            "text/html"
+        } else if is_element(first_element(&lower), "svg") {
+            "image/svg+xml"
+        } else if lower.starts_with("<?xml") {
+            "application/xml"
+        } else if looks_like_markdown(text) {
+            // Such as a README that starts with `<p align="center">`.
+            "text/markdown"
+        } else {
+            "text/html"
+        };
+        return Some(mime);
+    }
+
+    if looks_like_markdown(text) {
+        return Some("text/markdown");
+    }
+    Some("text/plain")
+}
+
+/// The text from the first element on, after the xml declaration, doctypes, comments and
+/// whitespace, or "" if there is none.
+fn first_element(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        let end = if text.starts_with("<?") {
+            text.find("?>").map(|i| i + 2)
+        } else if text.starts_with("<!--") {
+            text.find("-->").map(|i| i + 3)
+        } else if text.starts_with("<!") {
+            text.find('>').map(|i| i + 1)
+        } else {
+            return text;
+        };
+        match end {
+            Some(end) => text = &text[end..],
+            None => return "",
+        }
+    }
+}
+
+/// Whether text starts with the start tag of an element named `name`.
+fn is_element(text: &str, name: &str) -> bool {
+    text.strip_prefix('<')
+        .and_then(|t| t.strip_prefix(name))
+        .and_then(|t| t.chars().next())
+        .is_some_and(|c| c.is_ascii_whitespace() || c == '>' || c == '/')
+}
+
+/// Whether text has a heading, a code fence or a link of markdown, or two lines of lists,
+/// quotes or tables.
+fn looks_like_markdown(text: &str) -> bool {
+    let mut blocks = 0;
+
+    for line in text.lines() {
+        let line = line.trim_start();
+
+        let hashes = line.len() - line.trim_start_matches('#').len();
+        if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+            return true;
+        }
+        if line.starts_with("```") || line.starts_with("~~~") {
+            return true;
+        }
+        if line.contains("](") && line.contains('[') {
+            return true;
+        }
+
+        let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let is_block = ["- ", "* ", "+ ", "> ", "|"]
+            .iter()
+            .any(|p| line.starts_with(p))
+            || (digits > 0 && line[digits..].starts_with(". "));
+        if is_block {
+            blocks += 1;
+        }
+    }
+
+    blocks >= 2
+}
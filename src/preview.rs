@@ -0,0 +1,393 @@
+//! A local server to preview a directory of markdown, reloading pages when files change.
+
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::extract::Path as UrlPath;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use axum::http::Uri;
+use axum::response::sse::Event;
+use axum::response::sse::KeepAlive;
+use axum::response::sse::Sse;
+use axum::response::IntoResponse;
+use axum::response::Redirect;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher;
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::Stream;
+use tokio_stream::StreamExt;
+
+use crate::md::escape_html;
+use crate::md::MarkdownToHtml;
+use crate::md::MathOutput;
+use crate::render::batch::render_content;
+use crate::render::batch::RenderOptions;
+use crate::render::GITHUB_MARKDOWN_CSS;
+use crate::render::MARKDOWN_MIME;
+use crate::Mime;
+
+/// Where a page listens for reload events.
+const EVENTS_URL: &str = "/_xpmd/events";
+
+/// The prefix of the url of a page rendered to png, such as `/_xpmd/png/docs/intro.md`.
+const PNG_URL: &str = "/_xpmd/png/";
+
+const PREVIEW_CSS: &str = r#"
+body { margin: 0; }
+.preview-bar { position: fixed; top: 8px; right: 16px; font: 12px -apple-system, BlinkMacSystemFont, "Segoe UI", "Noto Sans", Helvetica, Arial, sans-serif; }
+.preview-bar a { color: #59636e; }
+.markdown-body { box-sizing: border-box; max-width: 980px; margin: 0 auto; padding: 45px; }
+"#;
+
+/// Reload the page on every event the server sends.
+const RELOAD_SCRIPT: &str =
+    r#"new EventSource("/_xpmd/events").onmessage = () => location.reload();"#;
+
+/// Serves the files in a directory: markdown as html pages converted with [`MarkdownToHtml`]
+/// and styled with `github-markdown.css`, a directory as a list of its files, and the other
+/// files as they are.
+///
+/// Pages reload when a file in the directory changes, and `/_xpmd/png/{path}` returns a page
+/// rendered to png by Chrome, as `xpmd render` would.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub root: PathBuf,
+    pub markdown: MarkdownToHtml,
+
+    /// How `/_xpmd/png/{path}` renders a page.
+    pub render: RenderOptions,
+}
+
+#[derive(Clone)]
+struct AppState {
+    preview: Arc<Preview>,
+    reload: broadcast::Sender<()>,
+}
+
+impl Preview {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            markdown: MarkdownToHtml::new().with_math(MathOutput::MathMl),
+            render: RenderOptions::default(),
+        }
+    }
+
+    /// Serve on `addr` until the process is stopped.
+    pub async fn serve(self, addr: SocketAddr) -> anyhow::Result<()> {
+        let (reload, _) = broadcast::channel(16);
+
+        // Reading a file, as serving it does, is an event too.
+        let tx = reload.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if event.is_ok_and(|e| !matches!(e.kind, EventKind::Access(_) | EventKind::Other)) {
+                    let _ = tx.send(());
+                }
+            })?;
+        watcher
+            .watch(&self.root, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch: {}", self.root.display()))?;
+
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to listen on {}", addr))?;
+        axum::serve(listener, self.router(reload)).await?;
+        Ok(())
+    }
+
+    /// The routes of the server; a message on `reload` reloads the open pages.
+    pub fn router(self, reload: broadcast::Sender<()>) -> Router {
+        let state = AppState {
+            preview: Arc::new(self),
+            reload,
+        };
+
+        Router::new()
+            .route(EVENTS_URL, get(events))
+            .route(&format!("{}{{*path}}", PNG_URL), get(png))
+            .fallback(get(file))
+            .with_state(state)
+    }
+
+    /// Respond to a request for `url_path`, such as `/docs/intro.md`.
+    pub async fn get(&self, url_path: &str) -> Response {
+        let Some(path) = self.resolve(url_path) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+
+        if path.is_dir() {
+            // Relative links in the page are resolved against the directory.
+            if !url_path.ends_with('/') {
+                return Redirect::permanent(&format!("{}/", url_path)).into_response();
+            }
+            for index in ["README.md", "index.md"] {
+                if path.join(index).is_file() {
+                    return self.page(&path.join(index), url_path).await;
+                }
+            }
+            return html_response(self.listing(&path, url_path));
+        }
+
+        if is_markdown(&path) {
+            return self.page(&path, url_path).await;
+        }
+
+        match fs::read(&path) {
+            Ok(data) => {
+                let (mime, encoding) = match Mime::from_path(&path) {
+                    // The browser decompresses a page or an image it shows, such as `.svgz`;
+                    // other compressed files, such as `.tar.gz`, are downloaded as they are.
+                    Some(m) if m.encoding.is_some() => {
+                        if m.mime.starts_with("image/") || m.mime.starts_with("text/") {
+                            (m.mime, m.encoding)
+                        } else {
+                            ("application/octet-stream", None)
+                        }
+                    }
+                    Some(m) => (m.mime, None),
+                    None => ("application/octet-stream", None),
+                };
+
+                let mut response = ([(header::CONTENT_TYPE, mime)], data).into_response();
+                if let Some(encoding) = encoding {
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+                }
+                response
+            }
+            Err(_) => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    /// The file a url path refers to, or `None` if it is outside of the root.
+    fn resolve(&self, url_path: &str) -> Option<PathBuf> {
+        let decoded = percent_encoding::percent_decode_str(url_path).decode_utf8_lossy();
+        let relative = Path::new(decoded.trim_start_matches('/'));
+
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+        Some(self.root.join(relative))
+    }
+
+    /// A markdown file as a page that reloads itself, with a link to it rendered to png.
+    async fn page(&self, path: &Path, url_path: &str) -> Response {
+        let md = match fs::read_to_string(path) {
+            Ok(md) => md,
+            Err(_) => return StatusCode::NOT_FOUND.into_response(),
+        };
+
+        let content = match self.markdown.render(&md).await {
+            Ok(html) => html,
+            Err(e) => format!("<pre>{}</pre>", escape_html(&format!("{:#}", e))),
+        };
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // A directory shows its index.
+        let index = if url_path.ends_with('/') { &name } else { "" };
+        let png_url = format!("{}{}{}", PNG_URL, url_path.trim_start_matches('/'), index);
+
+        html_response(page_html(
+            &name,
+            &format!(
+                r#"<nav class="preview-bar"><a href="{}">png</a></nav>"#,
+                png_url
+            ),
+            &content,
+        ))
+    }
+
+    /// A page listing the files of a directory.
+    fn listing(&self, dir: &Path, url_path: &str) -> String {
+        let mut entries = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') {
+                    return None;
+                }
+                Some(if e.path().is_dir() {
+                    format!("{}/", name)
+                } else {
+                    name
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        let mut items = String::new();
+        for name in &entries {
+            let href = percent_encoding::utf8_percent_encode(name, URL_PATH);
+            items.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                href,
+                escape_html(name)
+            ));
+        }
+
+        let title = escape_html(url_path);
+        page_html(
+            url_path,
+            "",
+            &format!("<h1>{}</h1>\n<ul>\n{}</ul>\n", title, items),
+        )
+    }
+}
+
+/// Characters to escape in a file name used as a url path.
+const URL_PATH: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?');
+
+fn page_html(title: &str, nav: &str, content: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>{}</style>
+<style>{}</style>
+</head>
+<body>
+{}
+<article class="markdown-body">
+{}</article>
+<script>{}</script>
+</body>
+</html>
+"#,
+        escape_html(title),
+        GITHUB_MARKDOWN_CSS,
+        PREVIEW_CSS,
+        nav,
+        content,
+        RELOAD_SCRIPT
+    )
+}
+
+fn html_response(html: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response()
+}
+
+fn is_markdown(path: &Path) -> bool {
+    Mime::from_path(path).is_some_and(|m| m.mime == MARKDOWN_MIME && m.encoding.is_none())
+}
+
+async fn file(State(state): State<AppState>, uri: Uri) -> Response {
+    state.preview.get(uri.path()).await
+}
+
+async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream =
+        BroadcastStream::new(state.reload.subscribe()).map(|_| Ok(Event::default().data("reload")));
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// A page rendered to png by Chrome, as `xpmd render` renders it.
+async fn png(State(state): State<AppState>, UrlPath(path): UrlPath<String>) -> Response {
+    let Some(path) = state.preview.resolve(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Ok(content) = fs::read_to_string(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let options = state.preview.render.clone();
+
+    // Chrome and ImageMagick are run as blocking commands.
+    let rendered = tokio::task::spawn_blocking(move || {
+        Handle::current().block_on(render_content(&path, &content, &options))
+    })
+    .await;
+
+    match rendered {
+        Ok(Ok(data)) => ([(header::CONTENT_TYPE, "image/png")], data).into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body(response: Response) -> (StatusCode, String) {
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("guide")).unwrap();
+        fs::write(tmp.path().join("guide/a b.md"), "# Hi\n\n> [!NOTE]\n> x\n").unwrap();
+        fs::write(tmp.path().join("guide/style.css"), "p {}").unwrap();
+        fs::write(tmp.path().join("guide/logo.svgz"), "").unwrap();
+        fs::write(tmp.path().join(".hidden"), "").unwrap();
+
+        let preview = Preview::new(tmp.path());
+
+        let (status, html) = body(preview.get("/").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<li><a href=\"guide/\">guide/</a></li>"));
+        assert!(!html.contains(".hidden"));
+
+        let response = preview.get("/guide").await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+
+        let (_, html) = body(preview.get("/guide/").await).await;
+        assert!(html.contains("<a href=\"a%20b.md\">a b.md</a>"));
+
+        let (status, html) = body(preview.get("/guide/a%20b.md").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<h1>Hi</h1>"));
+        assert!(html.contains("markdown-alert-note"));
+        assert!(html.contains("<a href=\"/_xpmd/png/guide/a%20b.md\">png</a>"));
+        assert!(html.contains(EVENTS_URL));
+
+        let response = preview.get("/guide/style.css").await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/css");
+
+        let response = preview.get("/guide/logo.svgz").await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+        let response = preview.get("/../etc/passwd").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = preview.get("/missing.md").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
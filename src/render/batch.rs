@@ -0,0 +1,472 @@
+//! Render many files in parallel, such as all pages matching `docs/**/*.html`.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use crate::md::MarkdownToHtml;
+use crate::md::MathOutput;
+use crate::render::content_mismatch;
+use crate::render::github_markdown_page;
+use crate::render::guess_markup_mime;
+use crate::render::report::seconds_since;
+use crate::render::report::RenderReport;
+use crate::render::with_chrome::WithChrome;
+use crate::render::MARKDOWN_MIME;
+use crate::Mime;
+use crate::MimeType;
+
+/// The default file name template of a batch render.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}.{format}";
+
+/// How to render a file.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Output format, one of [`crate::render::OUTPUT_FORMATS`].
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+
+    /// Mime type of the input, detected for each file if `None`.
+    pub mime: Option<String>,
+
+    /// Base path for assets referred to by relative paths.
+    pub base: Option<PathBuf>,
+
+    /// Device pixels per css pixel, such as 2 for an image twice as large and as sharp.
+    pub scale: f64,
+
+    /// Stylesheets added to html and markdown pages, after their own styles.
+    pub css: Vec<PathBuf>,
+
+    /// If set, the content comes from an untrusted source, such as a request to the render
+    /// service: it is rendered without `base` by [`WithChrome::render_untrusted`], which kills
+    /// Chrome after this long.
+    pub untrusted: Option<Duration>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            format: "png".to_string(),
+            width: 1000,
+            height: 2000,
+            mime: None,
+            base: None,
+            scale: 1.0,
+            css: vec![],
+            untrusted: None,
+        }
+    }
+}
+
+/// The outcome of rendering one file of a batch.
+#[derive(Debug)]
+pub struct Rendered {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub elapsed: Duration,
+
+    /// The size of the output in bytes, or why it failed.
+    pub result: anyhow::Result<usize>,
+
+    /// What it read, produced and took.
+    pub report: RenderReport,
+}
+
+/// Expand glob patterns, such as `docs/**/*.html`, to files, in the order given and without
+/// duplicates. A path without glob characters is kept even if it does not exist, so that
+/// rendering it reports the error.
+pub fn expand_inputs(patterns: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            files.push(PathBuf::from(pattern));
+            continue;
+        }
+
+        let before = files.len();
+        for entry in glob::glob(pattern).with_context(|| format!("Invalid glob: {}", pattern))? {
+            let path = entry?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        if files.len() == before {
+            anyhow::bail!("No files match: {}", pattern);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+    Ok(files)
+}
+
+/// The output path of `input` in `out_dir`, named by `template`, in which `{stem}` is the
+/// input file name without extension, `{format}` the output format and `{dir}` the directory
+/// of the input, such as `{dir}/{stem}.{format}` to keep the layout of the inputs.
+pub fn output_path(out_dir: &Path, template: &str, input: &Path, output_type: &str) -> PathBuf {
+    let file_stem = input.file_stem().unwrap_or_default().to_string_lossy();
+
+    // Only the normal components, so that the output stays in `out_dir`.
+    let input_dir = input
+        .parent()
+        .unwrap_or(Path::new(""))
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let name = template
+        .replace("{stem}", &file_stem)
+        .replace("{format}", output_type)
+        .replace("{dir}", &input_dir);
+
+    out_dir.join(name.trim_start_matches('/'))
+}
+
+/// Pair every input with its output path, failing if two inputs would be written to the same
+/// file.
+pub fn plan(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    template: &str,
+    format: &str,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut by_output: HashMap<PathBuf, &Path> = HashMap::new();
+    let mut pairs = vec![];
+
+    for input in inputs {
+        let output = output_path(out_dir, template, input, format);
+        if let Some(other) = by_output.insert(output.clone(), input) {
+            anyhow::bail!(
+                "{} and {} would both be written to {}; add {{dir}} to the name template",
+                other.display(),
+                input.display(),
+                output.display()
+            );
+        }
+        pairs.push((input.clone(), output));
+    }
+
+    Ok(pairs)
+}
+
+/// Read a file, render it and write the result, creating the output directory.
+///
+/// Returns the size of the output in bytes. How long each stage took, and what was produced,
+/// is recorded in `report`.
+pub async fn render_file(
+    input: &Path,
+    output: &Path,
+    options: &RenderOptions,
+    report: &mut RenderReport,
+) -> anyhow::Result<usize> {
+    let start = Instant::now();
+    let content = fs::read_to_string(input)
+        .with_context(|| format!("Failed to read input file: {}", input.display()))?;
+    report.durations.read = seconds_since(start);
+
+    let data = render_content_report(input, &content, options, report).await?;
+
+    let start = Instant::now();
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+    }
+    fs::write(output, &data)
+        .with_context(|| format!("Failed to write output file: {}", output.display()))?;
+    report.durations.write = seconds_since(start);
+
+    Ok(data.len())
+}
+
+/// Render the content read from `input`, whose extension decides the mime type if
+/// `options.mime` is not set.
+///
+/// Markdown is converted to a page styled as GitHub does, with assets relative to the
+/// directory of `input` unless `options.base` is set.
+pub async fn render_content(
+    input: &Path,
+    content: &str,
+    options: &RenderOptions,
+) -> anyhow::Result<Vec<u8>> {
+    render_content_report(input, content, options, &mut RenderReport::default()).await
+}
+
+/// Same as [`render_content`], recording the mime type, the output, how long converting and
+/// rendering took, and what may be wrong in `report`.
+pub async fn render_content_report(
+    input: &Path,
+    content: &str,
+    options: &RenderOptions,
+    report: &mut RenderReport,
+) -> anyhow::Result<Vec<u8>> {
+    let mime = match &options.mime {
+        Some(mime) => MimeType::parse_or_ext(mime)?,
+        None => guess_markup_mime(input, content).parse()?,
+    };
+    report.mime = Some(mime.to_string());
+
+    if options.mime.is_none() && input.extension().is_none() {
+        report.warnings.push(format!(
+            "The mime type {} is guessed from the content; set --mime to be sure",
+            mime
+        ));
+    }
+    if let Some(other) = Mime::from_path(input).and_then(|m| m.ambiguous) {
+        report.warnings.push(format!(
+            "The extensions of {} name {} too; set --mime to be sure",
+            input.display(),
+            other
+        ));
+    }
+    if let Some(sniffed) = content_mismatch(&mime, content.as_bytes()) {
+        report.warnings.push(format!(
+            "The content looks like {}, not {}",
+            sniffed,
+            mime.essence()
+        ));
+    }
+    if content.trim().is_empty() {
+        report.warnings.push("The input is empty".to_string());
+    }
+
+    let start = Instant::now();
+
+    let (mime, mut content, base) = if mime.is(MARKDOWN_MIME) {
+        let html = markdown_html(content, options).await?;
+        // The base is a `file://` url, which must be absolute.
+        let base = match &options.base {
+            _ if options.untrusted.is_some() => None,
+            Some(base) => Some(base.clone()),
+            None => {
+                let dir = input.parent().unwrap_or(Path::new(""));
+                Some(std::path::absolute(dir.join("."))?)
+            }
+        };
+        ("text/html".parse()?, github_markdown_page(&html), base)
+    } else {
+        (mime, content.to_string(), options.base.clone())
+    };
+
+    if mime.is_html() && !options.css.is_empty() {
+        content = add_stylesheets(&content, &options.css)?;
+    }
+    report.durations.convert = seconds_since(start);
+
+    report.chrome_version = WithChrome::chrome_version().ok();
+
+    let start = Instant::now();
+    let data = match options.untrusted {
+        Some(timeout) => {
+            WithChrome::render_untrusted(
+                &mime.to_string(),
+                &content,
+                &options.format.to_lowercase(),
+                Some(options.width),
+                Some(options.height),
+                options.scale,
+                timeout,
+            )
+            .await?
+        }
+        None => {
+            WithChrome::render_markup_scaled(
+                &mime.to_string(),
+                &content,
+                &options.format.to_lowercase(),
+                Some(options.width),
+                Some(options.height),
+                options.scale,
+                base.as_deref(),
+            )
+            .await?
+        }
+    };
+    report.durations.render = seconds_since(start);
+
+    report.set_output(&data, options.height, options.scale);
+    Ok(data)
+}
+
+/// Add the content of stylesheets to a html page, at the end of its `<head>` so that they
+/// override the styles of the page.
+fn add_stylesheets(html: &str, css: &[PathBuf]) -> anyhow::Result<String> {
+    let mut styles = String::new();
+    for path in css {
+        let css = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read stylesheet: {}", path.display()))?;
+        styles.push_str(&format!("<style>\n{}</style>\n", css));
+    }
+
+    Ok(match html.find("</head>") {
+        Some(i) => format!("{}{}{}", &html[..i], styles, &html[i..]),
+        None => format!("{}{}", styles, html),
+    })
+}
+
+/// Render `(input, output)` pairs with at most `jobs` at a time, going on after a failure.
+///
+/// The results are in the order of `pairs`.
+pub async fn render_batch(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    options: &RenderOptions,
+    jobs: usize,
+) -> anyhow::Result<Vec<Rendered>> {
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut tasks = vec![];
+
+    for (input, output) in pairs {
+        let permit = semaphore.clone().acquire_owned().await?;
+        let options = options.clone();
+
+        // Chrome and ImageMagick are run as blocking commands.
+        tasks.push(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let start = Instant::now();
+            let mut report = RenderReport::new(input.display(), output.display(), &options.format);
+            let result =
+                Handle::current().block_on(render_file(&input, &output, &options, &mut report));
+
+            report.durations.total = seconds_since(start);
+            if let Err(e) = &result {
+                report.set_error(e);
+            }
+            Rendered {
+                input,
+                output,
+                elapsed: start.elapsed(),
+                result,
+                report,
+            }
+        }));
+    }
+
+    let mut rendered = vec![];
+    for task in tasks {
+        rendered.push(task.await?);
+    }
+    Ok(rendered)
+}
+
+/// Convert markdown to html, keeping its raw html unless it comes from an untrusted source.
+async fn markdown_html(markdown: &str, options: &RenderOptions) -> anyhow::Result<String> {
+    MarkdownToHtml::new()
+        .with_math(MathOutput::MathMl)
+        .with_dangerous_html(options.untrusted.is_none())
+        .render(markdown)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        let out = Path::new("out");
+        let input = Path::new("../docs/guide/intro.page.html");
+
+        assert_eq!(
+            output_path(out, DEFAULT_NAME_TEMPLATE, input, "png"),
+            PathBuf::from("out/intro.page.png")
+        );
+        assert_eq!(
+            output_path(out, "{dir}/{stem}-{format}.{format}", input, "jpg"),
+            PathBuf::from("out/docs/guide/intro.page-jpg.jpg")
+        );
+        assert_eq!(
+            output_path(out, "{dir}/{stem}.{format}", Path::new("a.svg"), "png"),
+            PathBuf::from("out/a.png")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_markdown_html() {
+        let tmp = tempfile::tempdir().unwrap();
+        let readme = tmp.path().join("README.md");
+        fs::write(&readme, "<p align=\"center\">logo</p>\n\n# Title\n").unwrap();
+        let markdown = fs::read_to_string(&readme).unwrap();
+
+        let html = markdown_html(&markdown, &RenderOptions::default())
+            .await
+            .unwrap();
+        assert!(html.starts_with("<p align=\"center\">logo</p>\n"));
+
+        // The render service escapes it.
+        let untrusted = RenderOptions {
+            untrusted: Some(Duration::from_secs(1)),
+            ..RenderOptions::default()
+        };
+        let html = markdown_html(&markdown, &untrusted).await.unwrap();
+        assert!(html.starts_with("&lt;p align=&quot;center&quot;&gt;logo"));
+    }
+
+    #[test]
+    fn test_add_stylesheets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let css = tmp.path().join("a.css");
+        fs::write(&css, "p { color: red; }\n").unwrap();
+
+        assert_eq!(
+            add_stylesheets("<html><head><style>x</style></head></html>", &[css.clone()]).unwrap(),
+            "<html><head><style>x</style><style>\np { color: red; }\n</style>\n</head></html>"
+        );
+        assert_eq!(
+            add_stylesheets("<p>x</p>", &[css]).unwrap(),
+            "<style>\np { color: red; }\n</style>\n<p>x</p>"
+        );
+        assert!(add_stylesheets("", &[tmp.path().join("missing.css")]).is_err());
+    }
+
+    #[test]
+    fn test_plan_conflict() {
+        let inputs = [PathBuf::from("a/x.html"), PathBuf::from("b/x.svg")];
+        let out = Path::new("out");
+
+        let err = plan(&inputs, out, DEFAULT_NAME_TEMPLATE, "png").unwrap_err();
+        assert!(err.to_string().contains("out/x.png"));
+
+        let pairs = plan(&inputs, out, "{dir}/{stem}.{format}", "png").unwrap();
+        assert_eq!(pairs[1].1, PathBuf::from("out/b/x.png"));
+    }
+
+    #[test]
+    fn test_expand_inputs() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("sub")).unwrap();
+        for f in ["a.html", "b.svg", "sub/c.html"] {
+            fs::write(tmp.path().join(f), "").unwrap();
+        }
+
+        let d = tmp.path().display();
+        let files = expand_inputs(&[
+            format!("{}/**/*.html", d),
+            format!("{}/a.html", d),
+            format!("{}/missing.html", d),
+        ])
+        .unwrap();
+        assert_eq!(files, vec![
+            tmp.path().join("a.html"),
+            tmp.path().join("sub/c.html"),
+            tmp.path().join("missing.html"),
+        ]);
+
+        assert!(expand_inputs(&[format!("{}/*.png", d)]).is_err());
+    }
+}
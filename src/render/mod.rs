@@ -1 +1,205 @@
+pub mod batch;
+pub mod report;
+pub mod watch;
 pub mod with_chrome;
+
+use std::path::Path;
+
+use crate::mime::MARKUP_MIMES;
+use crate::Mime;
+use crate::MimeType;
+
+/// The GitHub markdown stylesheet, for pages showing html converted from markdown.
+pub const GITHUB_MARKDOWN_CSS: &str = include_str!("../../github-markdown.css");
+
+/// Wrap html converted from markdown into a page styled with [`GITHUB_MARKDOWN_CSS`].
+///
+/// The content is shrunk to fit so that a screenshot of the page can be trimmed to it.
+pub fn github_markdown_page(body: &str) -> String {
+    format!(
+        r#"<html>
+<head>
+<meta charset="utf-8">
+<style>{}</style>
+<style>.markdown-body {{ display: inline-block; box-sizing: border-box; max-width: 100%; padding: 8px; }}</style>
+</head>
+<body style="margin: 0;">
+<article class="markdown-body">{}</article>
+</body>
+</html>"#,
+        GITHUB_MARKDOWN_CSS, body
+    )
+}
+
+/// Return the trimmed content of `<body>` in a DOM dumped by chrome, or "" if there is none.
+pub(crate) fn dom_body(dom: &str) -> &str {
+    dom.split_once("<body>")
+        .and_then(|(_, rest)| rest.rsplit_once("</body>"))
+        .map(|(body, _)| body.trim())
+        .unwrap_or_default()
+}
+
+/// The output formats that rendering supports.
+pub const OUTPUT_FORMATS: &[&str] = &["png", "jpg", "jpeg", "pdf"];
+
+/// Fail if `format` is not one of [`OUTPUT_FORMATS`], in any case.
+pub fn check_format(format: &str) -> anyhow::Result<()> {
+    if OUTPUT_FORMATS.contains(&format.to_lowercase().as_str()) {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "Unsupported output format: {}. Supported: {}",
+        format,
+        OUTPUT_FORMATS.join(", ")
+    )
+}
+
+/// The output format to write to `output`: `format` if given, else the format whose mime type
+/// is that of the extension of `output`, such as "jpg" for `out.JPG`.
+///
+/// Returns `None` if neither tells, such as for stdout, to use the configured format. Fails if
+/// `format` and the extension conflict, or if the extension is of an unsupported format.
+pub fn output_format(format: Option<&str>, output: &Path) -> anyhow::Result<Option<String>> {
+    let format = format.map(|f| f.to_lowercase());
+    if let Some(f) = &format {
+        check_format(f)?;
+    }
+
+    let Some(ext) = output.extension().and_then(|e| e.to_str()) else {
+        return Ok(format);
+    };
+    let ext = ext.to_lowercase();
+    let Some(ext_mime) = Mime::get(&ext) else {
+        return Ok(format);
+    };
+
+    match format {
+        Some(f) if Mime::get(&f) == Some(ext_mime) => Ok(Some(f)),
+        Some(f) => anyhow::bail!(
+            "--format {} conflicts with the extension of the output: {}",
+            f,
+            output.display()
+        ),
+        None => {
+            // The extension itself if it is a format name, else another name of its type.
+            let found = OUTPUT_FORMATS.iter().find(|f| **f == ext).or_else(|| {
+                OUTPUT_FORMATS
+                    .iter()
+                    .find(|f| Mime::get(f) == Some(ext_mime))
+            });
+            match found {
+                Some(f) => Ok(Some(f.to_string())),
+                None => anyhow::bail!(
+                    "Unsupported output format: {} of {}. Supported: {}",
+                    ext,
+                    output.display(),
+                    OUTPUT_FORMATS.join(", ")
+                ),
+            }
+        }
+    }
+}
+
+/// The mime type of markdown, which is converted to html before rendering.
+pub const MARKDOWN_MIME: &str = "text/markdown";
+
+/// Guess the mime type of markup to render from the extension of its path, or from its content
+/// if the path has none, such as for stdin, or one that is not of markup.
+pub fn guess_markup_mime(path: &Path, content: &str) -> &'static str {
+    match Mime::from_path(path) {
+        Some(m) if m.encoding.is_none() && MARKUP_MIMES.contains(&m.mime) => m.mime,
+        _ => sniff_markup_mime(content),
+    }
+}
+
+/// The markup type [`Mime::sniff`] detects, or html for anything else.
+fn sniff_markup_mime(content: &str) -> &'static str {
+    Mime::sniff(content.as_bytes())
+        .filter(|mime| MARKUP_MIMES.contains(mime))
+        .unwrap_or("text/html")
+}
+
+/// The type [`Mime::sniff`] detects in `content` if it contradicts the declared `mime`, such as
+/// a png declared as html.
+///
+/// Plain text, and html in markdown, which markdown allows, do not contradict.
+pub fn content_mismatch(mime: &MimeType, content: &[u8]) -> Option<&'static str> {
+    let sniffed = Mime::sniff(content)?;
+    let compatible = mime.is(sniffed)
+        || sniffed == "text/plain"
+        || (mime.is(MARKDOWN_MIME) && sniffed == "text/html")
+        // svg is xml
+        || (mime.suffix.as_deref() == Some("xml") || mime.subtype == "xml")
+            && sniffed == "image/svg+xml";
+    (!compatible).then_some(sniffed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format() {
+        let format = |f: Option<&str>, output: &str| output_format(f, Path::new(output));
+
+        assert_eq!(format(None, "out.JPG").unwrap().as_deref(), Some("jpg"));
+        assert_eq!(format(None, "out.jpeg").unwrap().as_deref(), Some("jpeg"));
+        assert_eq!(format(None, "out.pdf").unwrap().as_deref(), Some("pdf"));
+        assert_eq!(format(None, "-").unwrap(), None);
+        assert_eq!(format(None, "out.xyz123").unwrap(), None);
+        assert_eq!(format(Some("PNG"), "-").unwrap().as_deref(), Some("png"));
+
+        // The same type by another name is not a conflict.
+        assert_eq!(
+            format(Some("jpeg"), "out.jpg").unwrap().as_deref(),
+            Some("jpeg")
+        );
+
+        let err = format(Some("png"), "out.jpg").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--format png conflicts with the extension of the output: out.jpg"
+        );
+        let err = format(None, "out.gif").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported output format: gif of out.gif. Supported: png, jpg, jpeg, pdf"
+        );
+        assert!(format(Some("gif"), "-").is_err());
+    }
+
+    #[test]
+    fn test_guess_markup_mime() {
+        let guess = |path: &str, content: &str| guess_markup_mime(Path::new(path), content);
+
+        assert_eq!(guess("a.SVG", "<p>x</p>"), "image/svg+xml");
+        assert_eq!(guess("-", "# Title\n"), MARKDOWN_MIME);
+        assert_eq!(guess("-", "<?xml version=\"1.0\"?><svg/>"), "image/svg+xml");
+        assert_eq!(guess("notes.txt", "- a\n- b\n"), MARKDOWN_MIME);
+        assert_eq!(guess("-", "plain text"), "text/html");
+        assert_eq!(guess("-", "<div><h1>x</h1><svg></svg></div>"), "text/html");
+        assert_eq!(guess("page.XHTML", ""), "application/xhtml+xml");
+        assert_eq!(guess("a.svgz", "<p>x</p>"), "text/html");
+    }
+
+    #[test]
+    fn test_content_mismatch() {
+        let mismatch = |mime: &str, content: &str| {
+            content_mismatch(&mime.parse().unwrap(), content.as_bytes())
+        };
+
+        assert_eq!(mismatch("text/html", "<svg/>"), Some("image/svg+xml"));
+        assert_eq!(
+            content_mismatch(&"image/svg+xml".parse().unwrap(), b"\x89PNG\r\n\x1a\n"),
+            Some("image/png")
+        );
+        assert_eq!(mismatch("text/markdown", "<div>x</div>"), None);
+        assert_eq!(mismatch("application/xml", "<svg/>"), None);
+        assert_eq!(mismatch("TEXT/HTML; charset=utf-8", "<p>x</p>"), None);
+        assert_eq!(mismatch("text/html", "just text"), None);
+        assert_eq!(
+            mismatch("text/html", "<div><h1>x</h1><svg></svg></div>"),
+            None
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! A structured record of one render, such as `xpmd render --json` prints.
+
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// What a render read, produced and took, or why it failed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenderReport {
+    pub input: String,
+    pub output: String,
+
+    /// The mime type the input is rendered as, such as "text/markdown".
+    pub mime: Option<String>,
+
+    /// Output format, one of [`crate::render::OUTPUT_FORMATS`].
+    pub format: String,
+
+    /// Size of the output image in pixels; `None` for pdf, or if it failed.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+
+    /// Size of the output in bytes.
+    pub bytes: Option<usize>,
+
+    /// Seconds spent in each stage.
+    pub durations: Durations,
+
+    pub chrome_version: Option<String>,
+
+    /// What may be wrong with an output that was written.
+    pub warnings: Vec<String>,
+
+    /// Why it failed; `None` if it succeeded.
+    pub error: Option<String>,
+}
+
+/// Seconds spent in each stage of a render; a stage that did not run is 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Durations {
+    /// Reading the input.
+    pub read: f64,
+
+    /// Converting markdown to html, and adding stylesheets.
+    pub convert: f64,
+
+    /// Taking the screenshot with Chrome and trimming it.
+    pub render: f64,
+
+    /// Writing the output.
+    pub write: f64,
+
+    pub total: f64,
+}
+
+impl RenderReport {
+    pub fn new(input: impl ToString, output: impl ToString, format: &str) -> Self {
+        Self {
+            input: input.to_string(),
+            output: output.to_string(),
+            format: format.to_lowercase(),
+            ..Self::default()
+        }
+    }
+
+    /// Record the output, and warn if it is as tall as the window, which means the content
+    /// may not fit.
+    pub fn set_output(&mut self, data: &[u8], window_height: u32, scale: f64) {
+        self.bytes = Some(data.len());
+
+        if let Some((width, height)) = image_size(data) {
+            self.width = Some(width);
+            self.height = Some(height);
+
+            let max_height = (window_height as f64 * scale).round() as u32;
+            if height >= max_height {
+                self.warnings.push(format!(
+                    "The image is as tall as the window ({}px); content below it is cut off, raise --height",
+                    height
+                ));
+            }
+        }
+    }
+
+    /// Record why the render failed.
+    pub fn set_error(&mut self, e: &anyhow::Error) {
+        self.error = Some(format!("{:#}", e));
+    }
+
+    /// The report as one line of JSON.
+    pub fn to_json(&self) -> String {
+        // Every field is a string, a number or a list of strings, which always serialize.
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Seconds since `start`.
+pub(crate) fn seconds_since(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64()
+}
+
+/// The `(width, height)` in pixels of a png or jpeg image, read from its header.
+pub fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // The IHDR chunk comes first: length, type, then width and height.
+        let be32 = |i: usize| Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?));
+        return Some((be32(16)?, be32(20)?));
+    }
+
+    if data.starts_with(b"\xff\xd8") {
+        // Walk the segments to the frame header: a SOF marker, other than DHT 0xc4, JPG 0xc8
+        // and DAC 0xcc, is followed by length, precision, height and width.
+        let be16 = |i: usize| Some(u16::from_be_bytes(data.get(i..i + 2)?.try_into().ok()?));
+        let mut i = 2;
+        while i + 4 <= data.len() {
+            if data[i] != 0xff {
+                return None;
+            }
+            let marker = data[i + 1];
+            if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+                return Some((be16(i + 7)? as u32, be16(i + 5)? as u32));
+            }
+            i += 2 + be16(i + 2)? as usize;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_size() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 3, 0xe8, 0, 0, 0, 200]);
+        assert_eq!(image_size(&png), Some((1000, 200)));
+
+        // SOI, an APP0 segment of 4 bytes, then SOF0 of a 640x480 image.
+        let jpeg = b"\xff\xd8\xff\xe0\0\x04ab\xff\xc0\0\x11\x08\x01\xe0\x02\x80";
+        assert_eq!(image_size(jpeg), Some((640, 480)));
+
+        assert_eq!(image_size(b"%PDF-1.4"), None);
+        assert_eq!(image_size(&png[..20]), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 3, 0xe8, 0, 0, 0x0f, 0xa0]);
+
+        let mut report = RenderReport::new("a.md", "a.png", "PNG");
+        report.set_output(&png, 2000, 2.0);
+        assert_eq!(report.format, "png");
+        assert_eq!((report.width, report.height), (Some(1000), Some(4000)));
+        assert_eq!(report.bytes, Some(24));
+        assert_eq!(report.warnings.len(), 1);
+
+        report.set_error(&anyhow::anyhow!("boom").context("Failed to render"));
+        let json = report.to_json();
+        assert!(json.starts_with(r#"{"input":"a.md","output":"a.png","mime":null,"format":"png","width":1000,"height":4000,"bytes":24,"durations":{"read":0.0,"#));
+        assert!(json.ends_with(r#""error":"Failed to render: boom"}"#));
+    }
+}
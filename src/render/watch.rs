@@ -0,0 +1,290 @@
+//! Render files again when they, or the files they use, change.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use markdown::mdast::Node;
+use notify::EventKind;
+use notify::RecursiveMode;
+use notify::Watcher;
+use tokio::sync::mpsc;
+
+use crate::export::assets::is_local;
+use crate::export::assets::resolve_local;
+use crate::md;
+use crate::render::batch::render_batch;
+use crate::render::batch::RenderOptions;
+use crate::render::batch::Rendered;
+use crate::render::guess_markup_mime;
+use crate::render::MARKDOWN_MIME;
+use crate::MimeType;
+
+/// How long to wait for more changes before rendering, so that saving several files, or an
+/// editor writing a file in steps, renders once.
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A file to render, and the files that make it render again when they change.
+#[derive(Debug, Clone)]
+struct Target {
+    input: PathBuf,
+    output: PathBuf,
+
+    /// Absolute paths of the input and the files it links to.
+    dependencies: BTreeSet<PathBuf>,
+}
+
+impl Target {
+    fn new(input: PathBuf, output: PathBuf, options: &RenderOptions) -> Self {
+        let mut target = Target {
+            input,
+            output,
+            dependencies: BTreeSet::new(),
+        };
+        target.refresh(options);
+        target
+    }
+
+    /// Read the input again to find what it links to now.
+    fn refresh(&mut self, options: &RenderOptions) {
+        let content = fs::read_to_string(&self.input).unwrap_or_default();
+        self.dependencies = dependencies(&self.input, &content, options)
+            .iter()
+            .map(|p| normalize(p))
+            .collect();
+    }
+}
+
+/// Render `(input, output)` pairs, then render them again whenever they change, until the
+/// process is stopped.
+///
+/// Besides an input itself, a change of a local stylesheet it links to, an image a markdown
+/// input shows, or any file in `options.base` renders it again. `report` is called with the
+/// result of every render.
+pub async fn watch(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    options: &RenderOptions,
+    jobs: usize,
+    mut report: impl FnMut(&Rendered),
+) -> anyhow::Result<()> {
+    let mut targets = pairs
+        .into_iter()
+        .map(|(input, output)| Target::new(input, output, options))
+        .collect::<Vec<_>>();
+
+    let outputs = targets
+        .iter()
+        .map(|t| normalize(&t.output))
+        .collect::<BTreeSet<_>>();
+    let base = options.base.as_deref().map(normalize);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })?;
+
+    if let Some(base) = &base {
+        watcher.watch(base, RecursiveMode::Recursive)?;
+    }
+
+    // Watch the directories rather than the files, because editors often save by replacing
+    // a file, after which a watch on the old file sees nothing.
+    let mut watched = BTreeSet::new();
+    let mut affected = (0..targets.len()).collect::<Vec<_>>();
+
+    loop {
+        let pairs = affected
+            .iter()
+            .map(|&i| (targets[i].input.clone(), targets[i].output.clone()))
+            .collect();
+        for r in render_batch(pairs, options, jobs).await? {
+            report(&r);
+        }
+
+        for &i in &affected {
+            targets[i].refresh(options);
+            for dir in targets[i].dependencies.iter().filter_map(|p| p.parent()) {
+                if dir.is_dir() && watched.insert(dir.to_path_buf()) {
+                    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                }
+            }
+        }
+
+        affected = vec![];
+        while affected.is_empty() {
+            let Some(event) = rx.recv().await else {
+                return Ok(());
+            };
+
+            // Collect changes until there is a pause.
+            let mut events = vec![event];
+            while let Ok(Some(event)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                events.push(event);
+            }
+
+            let mut changed = vec![];
+            for event in events {
+                let event: notify::Event = event?;
+                // Reading a file, as rendering does, is an event too.
+                if matches!(event.kind, EventKind::Access(_) | EventKind::Other) {
+                    continue;
+                }
+                changed.extend(
+                    event
+                        .paths
+                        .iter()
+                        .map(|p| normalize(p))
+                        .filter(|p| !outputs.contains(p)),
+                );
+            }
+
+            affected = (0..targets.len())
+                .filter(|&i| {
+                    changed.iter().any(|p| {
+                        targets[i].dependencies.contains(p)
+                            || base.as_ref().is_some_and(|b| p.starts_with(b))
+                    })
+                })
+                .collect();
+        }
+    }
+}
+
+/// The files that change how `input` renders: itself, the stylesheets in `options.css`, the
+/// local stylesheets it links to with `<link rel="stylesheet">`, and for markdown the local
+/// images it shows.
+pub fn dependencies(input: &Path, content: &str, options: &RenderOptions) -> Vec<PathBuf> {
+    let mime = match &options.mime {
+        Some(mime) => MimeType::parse_or_ext(mime).ok(),
+        None => guess_markup_mime(input, content).parse().ok(),
+    };
+
+    // Relative urls are resolved as `render_content` sets the base of the page.
+    let input_dir = input.parent().unwrap_or(Path::new(""));
+    let base_dir = options.base.as_deref().unwrap_or(input_dir);
+
+    let mut files = vec![input.to_path_buf()];
+    files.extend(options.css.iter().cloned());
+
+    for href in stylesheets(content) {
+        if is_local(&href) {
+            files.push(resolve_local(base_dir, &href));
+        }
+    }
+
+    if mime.is_some_and(|m: MimeType| m.is(MARKDOWN_MIME)) {
+        if let Ok(root) = md::parse(content) {
+            md::walk(&root, &mut |node| {
+                if let Node::Image(image) = node {
+                    if is_local(&image.url) {
+                        files.push(resolve_local(base_dir, &image.url));
+                    }
+                }
+            });
+        }
+    }
+
+    files
+}
+
+/// The `href` of every `<link rel="stylesheet">` in html.
+pub fn stylesheets(html: &str) -> Vec<String> {
+    // Tags and attribute names are case-insensitive; ascii lowercasing keeps byte offsets.
+    let lower = html.to_ascii_lowercase();
+    let mut hrefs = vec![];
+
+    for (i, _) in lower.match_indices("<link") {
+        let end = lower[i..].find('>').map_or(lower.len(), |e| i + e);
+        if !lower[i..end].contains("stylesheet") {
+            continue;
+        }
+        if let Some(href) = attr(&html[i..end], &lower[i..end], "href") {
+            hrefs.push(href.to_string());
+        }
+    }
+
+    hrefs
+}
+
+/// The value of a quoted attribute of a html tag, found by name in the lowercase `lower`.
+fn attr<'a>(tag: &'a str, lower: &str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let prefix = format!("{}={}", name, quote);
+        if let Some(i) = lower.find(&prefix) {
+            let value = &tag[i + prefix.len()..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+    }
+    None
+}
+
+/// An absolute path with symlinks in its directory resolved, to compare with the paths of
+/// change events. The file itself may not exist, such as after it is removed.
+fn normalize(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    match (path.parent().map(fs::canonicalize), path.file_name()) {
+        (Some(Ok(dir)), Some(name)) => dir.join(name),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(base: Option<&str>) -> RenderOptions {
+        RenderOptions {
+            base: base.map(PathBuf::from),
+            ..RenderOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_stylesheets() {
+        let html = r#"<link rel="stylesheet" href="a.css"><link rel=icon href="x.ico">
+<LINK REL='stylesheet' href='https://b.com/b.css' /><link href="c.css" rel="stylesheet">"#;
+        assert_eq!(stylesheets(html), vec![
+            "a.css",
+            "https://b.com/b.css",
+            "c.css"
+        ]);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let md = "<link rel=\"stylesheet\" href=\"style.css\">\n\n![a](img/a.png) ![b](https://b.com/b.png)\n";
+        assert_eq!(
+            dependencies(Path::new("docs/post.md"), md, &options(None)),
+            vec![
+                PathBuf::from("docs/post.md"),
+                PathBuf::from("docs/style.css"),
+                PathBuf::from("docs/img/a.png"),
+            ]
+        );
+
+        // Images are only looked for in markdown, and urls are relative to the base.
+        let html = "<link rel=\"stylesheet\" href=\"style.css\"><img src=\"a.png\">";
+        assert_eq!(
+            dependencies(Path::new("docs/page.html"), html, &options(Some("assets"))),
+            vec![
+                PathBuf::from("docs/page.html"),
+                PathBuf::from("assets/style.css"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().canonicalize().unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+
+        assert_eq!(
+            normalize(&dir.join("sub/../gone.css")),
+            dir.join("gone.css")
+        );
+    }
+}
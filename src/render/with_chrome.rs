@@ -2,11 +2,57 @@ use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::time::Duration;
 
 use anyhow::Context;
 use tempfile::TempDir;
 
 use crate::mime::Mime;
+use crate::mime::MimeType;
+
+/// Flags shared by every headless chrome invocation.
+const CHROME_FLAGS: &[&str] = &[
+    "--headless",
+    "--disable-gpu",
+    "--no-sandbox",
+    "--disable-dev-shm-usage",
+    "--disable-background-timer-throttling",
+    "--disable-backgrounding-occluded-windows",
+    "--disable-renderer-backgrounding",
+    "--disable-features=TranslateUI",
+    "--disable-ipc-flooding-protection",
+    "--disable-extensions",
+    "--no-first-run",
+    "--no-default-browser-check",
+    "--disable-features=VizDisplayCompositor",
+];
+
+/// Flags for trusted content only, which lets a page load local files, such as images and
+/// scripts, from any directory.
+const TRUSTED_FLAGS: &[&str] = &["--disable-web-security"];
+
+/// The policy of an untrusted page: no scripts, frames, requests or local files; only inline
+/// styles and `data:` images and fonts.
+const UNTRUSTED_CSP: &str = "default-src 'none'; style-src 'unsafe-inline' data:; img-src data:; font-src data:; base-uri 'none'; form-action 'none'";
+
+/// The Chrome executable set with [`WithChrome::set_chrome_path`].
+static CHROME_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// How a screenshot is taken.
+struct Snapshot<'a> {
+    width: Option<u32>,
+    height: Option<u32>,
+    scale: f64,
+    asset_base: Option<&'a Path>,
+
+    /// Kill Chrome if it runs longer than this, and lock down the page, for content from an
+    /// untrusted source.
+    untrusted: Option<Duration>,
+}
 
 pub struct WithChrome;
 
@@ -34,14 +80,71 @@ impl WithChrome {
         width: Option<u32>,
         height: Option<u32>,
         asset_base: Option<&Path>,
+    ) -> anyhow::Result<Vec<u8>> {
+        Self::render_markup_scaled(mime, input, output_type, width, height, 1.0, asset_base).await
+    }
+
+    /// Same as [`Self::render_markup`], with `scale` device pixels per css pixel, such as 2 for
+    /// an image as sharp as on a retina display. The image is `scale` times as large.
+    pub async fn render_markup_scaled(
+        mime: &str,
+        input: &str,
+        output_type: &str,
+        width: Option<u32>,
+        height: Option<u32>,
+        scale: f64,
+        asset_base: Option<&Path>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let snapshot = Snapshot {
+            width,
+            height,
+            scale,
+            asset_base,
+            untrusted: None,
+        };
+        Self::snapshot(mime, input, output_type, &snapshot).await
+    }
+
+    /// Same as [`Self::render_markup_scaled`], for content from an untrusted source, such as a
+    /// request to the render service.
+    ///
+    /// Html is shown in a sandboxed frame that can not run scripts, navigate or load anything
+    /// but `data:` urls, so local files stay out of the image; svg is shown as an image for the
+    /// same reason, and other types than html and svg are refused. Chrome is killed if it runs
+    /// longer than `timeout`.
+    pub async fn render_untrusted(
+        mime: &str,
+        input: &str,
+        output_type: &str,
+        width: Option<u32>,
+        height: Option<u32>,
+        scale: f64,
+        timeout: Duration,
+    ) -> anyhow::Result<Vec<u8>> {
+        let page = Self::untrusted_page(mime, input)?;
+        let snapshot = Snapshot {
+            width,
+            height,
+            scale,
+            asset_base: None,
+            untrusted: Some(timeout),
+        };
+        Self::snapshot("text/html", &page, output_type, &snapshot).await
+    }
+
+    async fn snapshot(
+        mime: &str,
+        input: &str,
+        output_type: &str,
+        snapshot: &Snapshot<'_>,
     ) -> anyhow::Result<Vec<u8>> {
         // Create temporary directory
         let temp_dir = TempDir::new()?;
         let cwd = temp_dir.path();
 
-        let input_file_path = Self::create_markup_file(cwd, input, mime, asset_base)?;
+        let input_file_path = Self::create_markup_file(cwd, input, mime, snapshot.asset_base)?;
 
-        let mut cmd = Self::build_chrome_snapshot_cmd(&input_file_path, width, height, cwd)?;
+        let mut cmd = Self::build_chrome_snapshot_cmd(&input_file_path, snapshot, cwd)?;
 
         let mes = format!(
             "Failed take snapshot with chrome: {:?}; cwd: {}",
@@ -53,33 +156,147 @@ impl WithChrome {
         cmd.current_dir(cwd);
         cmd.env("DISPLAY", ":99"); // Virtual display for headless CI
 
-        let chrome_status = cmd.status().context(mes.clone())?;
+        // Keep stdout clean for the caller, such as `xpmd render -o -` writing the image to it,
+        // and stderr for warnings, such as of `--json`: Chrome only reports the screenshot there.
+        cmd.stdout(Stdio::null());
 
-        println!("chrome_status: {:?}; cmd: {:?}", chrome_status, cmd);
+        let chrome_status = status_with_timeout(cmd, snapshot.untrusted)
+            .await
+            .context(mes.clone())?;
 
         if !chrome_status.success() {
             anyhow::bail!("{}: exit code: {:?}", mes, chrome_status.code());
         }
 
-        println!("chrome_status success: {:?}; cmd: {:?}", chrome_status, cmd);
-
         // The default screenshot path.
         let screenshot_path = cwd.join("screenshot.png");
 
-        // show the content of cwd dir for debug
-        println!("cwd: {}", cwd.display());
-        let files = fs::read_dir(cwd).context("Failed to read cwd")?;
-        for file in files {
-            let file = file?;
-            println!("{}", file.path().display());
-        }
-
         // Process the screenshot based on output type
         let final_image_data = Self::trim_image(&screenshot_path, output_type)?;
 
         Ok(final_image_data)
     }
 
+    /// Load content in headless chrome, let its scripts run, and return the serialized DOM.
+    ///
+    /// It is used to run js libraries such as mermaid in a page and collect what they produce.
+    ///
+    /// # Arguments
+    ///
+    /// * `mime` - a full mime type such as "text/html" or a shortcut "html"
+    /// * `input` - content of the input, such as html source
+    /// * `asset_base` - specifies the path to assets dir. E.g. the script base path in a html page
+    /// * `budget_ms` - how long in virtual time to wait for scripts. Default 30000
+    ///
+    /// # Returns
+    ///
+    /// the html serialization of the DOM
+    pub async fn dump_dom(
+        mime: &str,
+        input: &str,
+        asset_base: Option<&Path>,
+        budget_ms: Option<u32>,
+    ) -> anyhow::Result<String> {
+        let temp_dir = TempDir::new()?;
+        let cwd = temp_dir.path();
+
+        let input_file_path = Self::create_markup_file(cwd, input, mime, asset_base)?;
+
+        let mut cmd = Self::build_chrome_dump_dom_cmd(&input_file_path, budget_ms, cwd)?;
+        cmd.env("DISPLAY", ":99"); // Virtual display for headless CI
+
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to dump DOM with chrome: {:?}", cmd))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Failed to dump DOM with chrome: {:?}: {}", cmd, stderr);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Print content to pdf with headless chrome, paginated as chrome prints a page.
+    ///
+    /// # Arguments
+    ///
+    /// * `mime` - a full mime type such as "text/html" or a shortcut "html"
+    /// * `input` - content of the input, such as html source
+    /// * `asset_base` - specifies the path to assets dir. E.g. the image base path in a html page
+    ///
+    /// # Returns
+    ///
+    /// bytes of the pdf
+    pub async fn print_pdf(
+        mime: &str,
+        input: &str,
+        asset_base: Option<&Path>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let temp_dir = TempDir::new()?;
+        let cwd = temp_dir.path();
+
+        let input_file_path = Self::create_markup_file(cwd, input, mime, asset_base)?;
+        let pdf_path = cwd.join("output.pdf");
+
+        let mut cmd = Self::build_chrome_print_pdf_cmd(&input_file_path, &pdf_path, cwd)?;
+        cmd.env("DISPLAY", ":99"); // Virtual display for headless CI
+
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to print pdf with chrome: {:?}", cmd))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("Failed to print pdf with chrome: {:?}: {}", cmd, stderr);
+        }
+
+        fs::read(&pdf_path)
+            .with_context(|| format!("Chrome did not write the pdf: {}", pdf_path.display()))
+    }
+
+    /// Build a html page that shows untrusted content without running it or loading anything.
+    ///
+    /// Html is put in an `<iframe sandbox srcdoc>`, which runs no scripts and can not navigate
+    /// the page, under [`UNTRUSTED_CSP`], which also forbids the frame to load or navigate to
+    /// anything but `data:` urls. Html with a `<meta http-equiv>`, such as a refresh to a
+    /// `file://` url, is refused: a policy does not cover navigation.
+    fn untrusted_page(mime: &str, input: &str) -> anyhow::Result<String> {
+        let mime = MimeType::parse_or_ext(mime)?;
+        let csp = format!(
+            r#"<meta http-equiv="Content-Security-Policy" content="{}">"#,
+            UNTRUSTED_CSP
+        );
+
+        let body = if mime.is_html() {
+            if has_http_equiv(input) {
+                anyhow::bail!(
+                    "Untrusted html must not have a <meta http-equiv>, such as a refresh"
+                );
+            }
+            format!(
+                r#"<iframe sandbox srcdoc="{}" style="display: block; width: 100vw; height: 100vh; border: 0;"></iframe>"#,
+                crate::md::escape_html(&format!("{}{}", csp, input))
+            )
+        } else if mime.is("image/svg+xml") {
+            // An svg shown by `<img>` runs no scripts and loads nothing.
+            format!(
+                r#"<img src="{}">"#,
+                crate::md::data_url("svg", input.as_bytes())
+            )
+        } else {
+            anyhow::bail!(
+                "Unsupported mime type of untrusted content: {}. Supported: html, svg",
+                mime
+            )
+        };
+
+        Ok(format!(
+            r#"<!DOCTYPE html><html><head>{}</head><body style="margin: 0;">{}</body></html>"#,
+            csp, body
+        ))
+    }
+
     /// Setup html context, such as encoding and url base
     fn setup_html_page_context(input: &str, asset_base: Option<&Path>) -> String {
         let meta_tag = r#"<meta http-equiv="Content-Type" content="text/html; charset=utf-8"/>"#;
@@ -103,12 +320,54 @@ impl WithChrome {
             return suffix.to_string();
         }
 
+        // Then the type in lowercase and without parameters, else its subtype, such as "x-foo"
+        // of "text/x-foo"
+        if let Ok(mime) = mime.parse::<MimeType>() {
+            return mime.extension().map_or(mime.subtype, str::to_string);
+        }
+
         // Fallback to the mime parameter itself as suffix
         mime.to_string()
     }
 
+    /// Use this Chrome executable instead of searching for one, such as set in `xpmd.toml`.
+    pub fn set_chrome_path(path: impl Into<PathBuf>) {
+        *CHROME_PATH.write().unwrap() = Some(path.into());
+    }
+
+    /// Find Chrome executable, probing once per process.
+    pub fn find_chrome_executable() -> anyhow::Result<String> {
+        if let Some(path) = CHROME_PATH.read().unwrap().as_ref() {
+            return Ok(path.display().to_string());
+        }
+
+        static CHROME: OnceLock<Result<String, String>> = OnceLock::new();
+        probe_once(&CHROME, Self::probe_chrome_executable)
+    }
+
+    /// The version Chrome reports, such as "Chromium 131.0.6778.85", probing once per process.
+    pub fn chrome_version() -> anyhow::Result<String> {
+        static VERSION: OnceLock<Result<String, String>> = OnceLock::new();
+        probe_once(&VERSION, || {
+            Self::command_version(&Self::find_chrome_executable()?, "--version")
+        })
+    }
+
+    /// The first line a program prints for a version flag.
+    pub(crate) fn command_version(program: &str, flag: &str) -> anyhow::Result<String> {
+        let output = Command::new(program)
+            .arg(flag)
+            .output()
+            .with_context(|| format!("Failed to run {}", program))?;
+        if !output.status.success() {
+            anyhow::bail!("`{} {}` failed: {}", program, flag, output.status);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().next().unwrap_or_default().trim().to_string())
+    }
+
     /// Find Chrome executable by checking common paths
-    fn find_chrome_executable() -> anyhow::Result<String> {
+    fn probe_chrome_executable() -> anyhow::Result<String> {
         // Check macOS Chrome path first
         let mac_chrome = "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome";
         if Path::new(mac_chrome).exists() {
@@ -162,14 +421,17 @@ impl WithChrome {
         mime: &str,
         asset_base: Option<&Path>,
     ) -> anyhow::Result<PathBuf> {
+        let mime = MimeType::parse_or_ext(mime)?;
+
         // Process input content
-        let markup_content = if mime.contains("html") {
+        let markup_content = if mime.is_html() {
             Self::setup_html_page_context(markup_content, asset_base)
         } else {
             markup_content.to_string()
         };
 
-        let suffix = Self::get_file_suffix(mime);
+        // Chrome tells the type of the file by its suffix.
+        let suffix = Self::get_file_suffix(&mime.essence());
         let markup_file_path = base_dir.join(format!("input.{}", suffix));
 
         fs::write(&markup_file_path, markup_content.as_bytes()).with_context(|| {
@@ -182,34 +444,25 @@ impl WithChrome {
     /// Build a chrome command to take screenshot, the output is a png file "screenshot.png" in the current directory
     fn build_chrome_snapshot_cmd(
         markup_file_path: &Path,
-        width: Option<u32>,
-        height: Option<u32>,
+        snapshot: &Snapshot<'_>,
         cwd: &Path,
     ) -> anyhow::Result<Command> {
-        let width = width.unwrap_or(1000);
-        let height = height.unwrap_or(2000);
+        let width = snapshot.width.unwrap_or(1000);
+        let height = snapshot.height.unwrap_or(2000);
+        let scale = snapshot.scale;
 
         let chrome_path = Self::find_chrome_executable()?;
 
         let mut cmd = Command::new(chrome_path);
 
-        cmd.args(vec![
-            "--headless",
-            "--disable-gpu",
-            "--no-sandbox",
-            "--disable-dev-shm-usage",
-            "--disable-background-timer-throttling",
-            "--disable-backgrounding-occluded-windows",
-            "--disable-renderer-backgrounding",
-            "--disable-features=TranslateUI",
-            "--disable-ipc-flooding-protection",
-            "--disable-extensions",
-            "--no-first-run",
-            "--no-default-browser-check",
-            "--disable-web-security",
-            "--disable-features=VizDisplayCompositor",
+        cmd.args(CHROME_FLAGS);
+        if snapshot.untrusted.is_none() {
+            cmd.args(TRUSTED_FLAGS);
+        }
+        cmd.args([
             "--screenshot",
             &format!("--window-size={},{}", width, height),
+            &format!("--force-device-scale-factor={}", scale),
             "--default-background-color=00000000",
             markup_file_path.to_str().unwrap(),
         ])
@@ -218,42 +471,82 @@ impl WithChrome {
         Ok(cmd)
     }
 
+    /// Build a chrome command that prints the serialized DOM to stdout after scripts finish,
+    /// or after `budget_ms` milliseconds of virtual time.
+    fn build_chrome_dump_dom_cmd(
+        markup_file_path: &Path,
+        budget_ms: Option<u32>,
+        cwd: &Path,
+    ) -> anyhow::Result<Command> {
+        let budget_ms = budget_ms.unwrap_or(30_000);
+
+        let chrome_path = Self::find_chrome_executable()?;
+
+        let mut cmd = Command::new(chrome_path);
+
+        cmd.args(CHROME_FLAGS)
+            .args(TRUSTED_FLAGS)
+            .args([
+                "--dump-dom",
+                &format!("--virtual-time-budget={}", budget_ms),
+                markup_file_path.to_str().unwrap(),
+            ])
+            .current_dir(cwd);
+
+        Ok(cmd)
+    }
+
+    /// Build a chrome command that prints the page to `pdf_path`, without header and footer.
+    fn build_chrome_print_pdf_cmd(
+        markup_file_path: &Path,
+        pdf_path: &Path,
+        cwd: &Path,
+    ) -> anyhow::Result<Command> {
+        let chrome_path = Self::find_chrome_executable()?;
+
+        let mut cmd = Command::new(chrome_path);
+
+        cmd.args(CHROME_FLAGS)
+            .args(TRUSTED_FLAGS)
+            .args([
+                &format!("--print-to-pdf={}", pdf_path.display()),
+                "--no-pdf-header-footer",
+                markup_file_path.to_str().unwrap(),
+            ])
+            .current_dir(cwd);
+
+        Ok(cmd)
+    }
+
     /// Return the first available command from a list
-    fn find_available_command(commands: &[&str]) -> anyhow::Result<String> {
+    pub(crate) fn find_available_command(commands: &[&str]) -> anyhow::Result<String> {
         for cmd in commands {
-            // output debug info about the command:
-            let mut probe = Command::new("which");
-            probe.arg(cmd);
-
-            let output = probe.output().unwrap();
-
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-
-            println!("--------------------------------");
-            println!("command: {:?}", probe);
-            println!("exit code: {}", output.status);
-            println!("stdout:");
-            println!("{}", stdout);
-            println!("stderr:");
-            println!("{}", stderr);
-            println!("--------------------------------");
-
-            if output.status.success() {
-                println!("Found command: {} at {}", cmd, stdout);
+            let found = Command::new("which")
+                .arg(cmd)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if found {
                 return Ok(cmd.to_string());
             }
         }
         anyhow::bail!("No available command found in PATH: {:?}", commands)
     }
 
-    /// Build a ImageMagick command to trim image that output directly to stdout
-    fn build_trim_image_cmd(screenshot_path: &Path, output_type: &str) -> anyhow::Result<Command> {
+    /// Find the ImageMagick command, probing once per process.
+    pub fn find_image_magick() -> anyhow::Result<String> {
         // Find the first available `convert` command:
         // ImageMagick's `convert` command is deprecated and replaced by `magick convert`
-        let commands = ["magick", "convert"];
+        static IMAGE_MAGICK: OnceLock<Result<String, String>> = OnceLock::new();
+        probe_once(&IMAGE_MAGICK, || {
+            Self::find_available_command(&["magick", "convert"])
+        })
+    }
 
-        let executable = Self::find_available_command(&commands)?;
+    /// Build a ImageMagick command to trim image that output directly to stdout
+    fn build_trim_image_cmd(screenshot_path: &Path, output_type: &str) -> anyhow::Result<Command> {
+        let executable = Self::find_image_magick()?;
 
         let mut cmd = Command::new(executable);
         cmd.arg(screenshot_path).arg("-trim").arg("+repage");
@@ -272,9 +565,54 @@ impl WithChrome {
     }
 }
 
+/// Whether html has a `<meta>` tag with an `http-equiv` attribute, in any case.
+fn has_http_equiv(html: &str) -> bool {
+    // Attribute names can not be escaped, unlike their values.
+    let lower = html.to_ascii_lowercase();
+    lower.match_indices("<meta").any(|(i, _)| {
+        let end = lower[i..].find('>').map_or(lower.len(), |e| i + e);
+        lower[i..end].contains("http-equiv")
+    })
+}
+
+/// Run a command to the end, or kill it if it runs longer than `timeout`, without blocking
+/// the async runtime while waiting.
+async fn status_with_timeout(
+    cmd: Command,
+    timeout: Option<Duration>,
+) -> anyhow::Result<ExitStatus> {
+    let mut cmd = tokio::process::Command::from(cmd);
+    // Also kill Chrome if the render is cancelled, such as when the request is dropped.
+    cmd.kill_on_drop(true);
+    let mut child = cmd.spawn()?;
+
+    let Some(timeout) = timeout else {
+        return Ok(child.wait().await?);
+    };
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => Ok(status?),
+        Err(_) => {
+            child.kill().await?;
+            anyhow::bail!("Killed after {:?}", timeout);
+        }
+    }
+}
+
+/// Run a probe for an external program the first time, and return its result from then on,
+/// so that rendering many files does not search `PATH` for each.
+fn probe_once(
+    cell: &OnceLock<Result<String, String>>,
+    probe: impl FnOnce() -> anyhow::Result<String>,
+) -> anyhow::Result<String> {
+    cell.get_or_init(|| probe().map_err(|e| format!("{:#}", e)))
+        .clone()
+        .map_err(anyhow::Error::msg)
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::time::Instant;
 
     use super::*;
 
@@ -301,9 +639,66 @@ mod tests {
         // Test known MIME types
         assert_eq!(WithChrome::get_file_suffix("text/html"), "html");
 
+        assert_eq!(WithChrome::get_file_suffix("TEXT/HTML"), "html");
+        assert_eq!(WithChrome::get_file_suffix("text/x-foo"), "x-foo");
+
         // Test fallback
         assert_eq!(WithChrome::get_file_suffix("custom"), "custom");
     }
 
+    #[test]
+    fn test_untrusted_page() {
+        let csp = format!(
+            r#"<meta http-equiv="Content-Security-Policy" content="{}">"#,
+            UNTRUSTED_CSP
+        );
+
+        // The html is only in the sandboxed frame, escaped as an attribute value.
+        let page =
+            WithChrome::untrusted_page("text/html", r#"<p class="a">x &amp; y</p>"#).unwrap();
+        assert!(page.starts_with(&format!("<!DOCTYPE html><html><head>{}</head>", csp)));
+        assert!(page.contains(&format!(
+            r#"<iframe sandbox srcdoc="{}&lt;p class=&quot;a&quot;&gt;x &amp;amp; y&lt;/p&gt;" "#,
+            crate::md::escape_html(&csp)
+        )));
+        assert!(!page.contains("<p"));
+
+        let page = WithChrome::untrusted_page("svg", "<svg/>").unwrap();
+        assert!(page.contains(r#"<img src="data:image/svg+xml;base64,PHN2Zy8+">"#));
+        assert!(!page.contains("<iframe"));
+
+        assert!(WithChrome::untrusted_page("application/xml", "<a/>").is_err());
+    }
+
+    #[test]
+    fn test_untrusted_page_refresh() {
+        for html in [
+            r#"<meta http-equiv="refresh" content="0;url=file:///etc/passwd">"#,
+            "<p>x</p><META HTTP-EQUIV=Refresh CONTENT='0;url=file:///etc/passwd'>",
+            r#"<meta content="0;url=file:///etc/passwd" http-equiv="&#114;efresh">"#,
+        ] {
+            let err = WithChrome::untrusted_page("text/html", html).unwrap_err();
+            assert!(err.to_string().contains("http-equiv"), "{}", html);
+        }
+
+        // Text about it is not a tag.
+        assert!(WithChrome::untrusted_page("html", "<p>http-equiv=refresh</p>").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_status_with_timeout() {
+        let start = Instant::now();
+        let mut sleep = Command::new("sleep");
+        sleep.arg("10");
+        let err = status_with_timeout(sleep, Some(Duration::from_millis(100)))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Killed after"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let status = status_with_timeout(Command::new("true"), Some(Duration::from_secs(5))).await;
+        assert!(status.unwrap().success());
+    }
+
     // Note: Integration tests require Chrome and ImageMagick to be installed
 }
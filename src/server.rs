@@ -0,0 +1,385 @@
+//! A http service that renders markup posted to it, for programs that do not run `xpmd`.
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::body::Bytes;
+use axum::extract::DefaultBodyLimit;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Router;
+use serde::Deserialize;
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use crate::render::batch::render_content;
+use crate::render::batch::RenderOptions;
+use crate::render::check_format;
+use crate::Mime;
+use crate::MimeType;
+
+/// The largest width or height of the window, in css pixels.
+const MAX_SIZE: u32 = 10_000;
+
+/// The most device pixels of an image: `width * height * scale²`.
+const MAX_PIXELS: f64 = 50_000_000.0;
+
+/// Renders the body of `POST /render` with the same code as `xpmd render`, and answers
+/// `GET /health` with "ok".
+///
+/// The query of `/render` sets how to render, all optional:
+/// `?mime=text/html&format=png&width=1000&height=2000&scale=2`. The mime type can also be a
+/// shortcut such as `svg` or `md`; without one, the `Content-Type` of the request is used,
+/// or the type is guessed from the content.
+///
+/// The body is untrusted: it is rendered with [`WithChrome::render_untrusted`], which loads
+/// no local files and runs no scripts, and the size of the image is limited.
+///
+/// [`WithChrome::render_untrusted`]: crate::render::with_chrome::WithChrome::render_untrusted
+#[derive(Debug, Clone)]
+pub struct RenderServer {
+    /// The largest body accepted, in bytes. A larger one is answered with 413.
+    pub max_body: usize,
+
+    /// How many renders run at a time; more requests wait for their turn.
+    pub concurrency: usize,
+
+    /// How long a render may take; Chrome is killed after it, and the request answered
+    /// with 500.
+    pub timeout: Duration,
+
+    /// How to render what the query does not set.
+    pub defaults: RenderOptions,
+}
+
+impl Default for RenderServer {
+    fn default() -> Self {
+        Self {
+            max_body: 10 * 1024 * 1024,
+            concurrency: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            timeout: Duration::from_secs(60),
+            defaults: RenderOptions::default(),
+        }
+    }
+}
+
+/// The query of `POST /render`.
+#[derive(Debug, Default, Deserialize)]
+struct RenderQuery {
+    mime: Option<String>,
+    format: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    scale: Option<f64>,
+}
+
+#[derive(Clone)]
+struct AppState {
+    permits: Arc<Semaphore>,
+    defaults: Arc<RenderOptions>,
+}
+
+impl RenderServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve on `addr` until the process is stopped.
+    pub async fn serve(self, addr: SocketAddr) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to listen on {}", addr))?;
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    pub fn router(self) -> Router {
+        let state = AppState {
+            permits: Arc::new(Semaphore::new(self.concurrency.max(1))),
+            defaults: Arc::new(RenderOptions {
+                untrusted: Some(self.timeout),
+                ..self.defaults
+            }),
+        };
+
+        Router::new()
+            .route("/health", get(health))
+            .route("/render", post(render))
+            .layer(DefaultBodyLimit::max(self.max_body))
+            .with_state(state)
+    }
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+async fn render(
+    State(state): State<AppState>,
+    Query(query): Query<RenderQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let options = match render_options(query, &headers, &state.defaults) {
+        Ok(options) => options,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("{:#}", e)).into_response(),
+    };
+    let Ok(content) = String::from_utf8(body.to_vec()) else {
+        return (StatusCode::BAD_REQUEST, "The body is not utf-8").into_response();
+    };
+
+    let Ok(_permit) = state.permits.acquire().await else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    let format = options.format.clone();
+
+    // Chrome and ImageMagick are run as blocking commands.
+    let rendered = tokio::task::spawn_blocking(move || {
+        Handle::current().block_on(render_content(Path::new(""), &content, &options))
+    })
+    .await;
+
+    match rendered {
+        Ok(Ok(data)) => {
+            let mime = Mime::get_or_fallback(&format).to_string();
+            ([(header::CONTENT_TYPE, mime)], data).into_response()
+        }
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Build the options of a render from the query, and `defaults` for what it does not set.
+fn render_options(
+    query: RenderQuery,
+    headers: &HeaderMap,
+    defaults: &RenderOptions,
+) -> anyhow::Result<RenderOptions> {
+    let format = query
+        .format
+        .unwrap_or_else(|| defaults.format.clone())
+        .to_lowercase();
+    check_format(&format)?;
+
+    let scale = query.scale.unwrap_or(defaults.scale);
+    if !(scale > 0.0 && scale <= 8.0) {
+        anyhow::bail!(
+            "Unsupported scale: {}. Supported: greater than 0, up to 8",
+            scale
+        );
+    }
+
+    let width = query.width.unwrap_or(defaults.width);
+    let height = query.height.unwrap_or(defaults.height);
+    for (name, size) in [("width", width), ("height", height)] {
+        if !(1..=MAX_SIZE).contains(&size) {
+            anyhow::bail!(
+                "Unsupported {}: {}. Supported: 1 to {}",
+                name,
+                size,
+                MAX_SIZE
+            );
+        }
+    }
+    let pixels = width as f64 * height as f64 * scale * scale;
+    if pixels > MAX_PIXELS {
+        anyhow::bail!(
+            "The image would have {} pixels at width {}, height {} and scale {}. Supported: up to {}",
+            pixels,
+            width,
+            height,
+            scale,
+            MAX_PIXELS
+        );
+    }
+
+    // A form post is not a mime type of markup.
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.starts_with("application/x-www-form-urlencoded"));
+
+    let mime = match query.mime.as_deref().or(content_type) {
+        // Parameters such as "; charset=utf-8" are not part of the type.
+        Some(mime) => Some(MimeType::parse_or_ext(mime)?.essence()),
+        None => defaults.mime.clone(),
+    };
+
+    // Posted content must not read local files relative to a base.
+    Ok(RenderOptions {
+        format,
+        width,
+        height,
+        mime,
+        scale,
+        base: None,
+        ..defaults.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn test_render_options() {
+        let query = RenderQuery {
+            mime: Some("MD".to_string()),
+            format: Some("JPG".to_string()),
+            width: Some(600),
+            scale: Some(2.0),
+            ..RenderQuery::default()
+        };
+        let options = render_options(query, &HeaderMap::new(), &RenderOptions::default()).unwrap();
+        assert_eq!(options.mime.as_deref(), Some("text/markdown"));
+        assert_eq!(options.format, "jpg");
+        assert_eq!(
+            (options.width, options.height, options.scale),
+            (600, 2000, 2.0)
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            "image/svg+xml; charset=utf-8".parse().unwrap(),
+        );
+        let options =
+            render_options(RenderQuery::default(), &headers, &RenderOptions::default()).unwrap();
+        assert_eq!(options.mime.as_deref(), Some("image/svg+xml"));
+
+        let bad = |query: RenderQuery| {
+            render_options(query, &HeaderMap::new(), &RenderOptions::default()).is_err()
+        };
+        assert!(bad(RenderQuery {
+            format: Some("gif".to_string()),
+            ..RenderQuery::default()
+        }));
+        assert!(bad(RenderQuery {
+            scale: Some(0.0),
+            ..RenderQuery::default()
+        }));
+        assert!(bad(RenderQuery {
+            mime: Some("nope".to_string()),
+            ..RenderQuery::default()
+        }));
+    }
+
+    #[test]
+    fn test_render_options_limits() {
+        let query = |width: u32, height: u32, scale: f64| RenderQuery {
+            width: Some(width),
+            height: Some(height),
+            scale: Some(scale),
+            ..RenderQuery::default()
+        };
+        let defaults = RenderOptions {
+            base: Some("/srv".into()),
+            untrusted: Some(Duration::from_secs(1)),
+            ..RenderOptions::default()
+        };
+        let options = |q| render_options(q, &HeaderMap::new(), &defaults);
+
+        let ok = options(query(10_000, 5_000, 1.0)).unwrap();
+        assert_eq!(ok.base, None);
+        assert_eq!(ok.untrusted, Some(Duration::from_secs(1)));
+
+        for (width, height, scale) in [
+            (10_001, 100, 1.0),
+            (100, 10_001, 1.0),
+            (0, 100, 1.0),
+            (100, 0, 1.0),
+            (100_000, 100_000, 1.0),
+            (5_000, 5_000, 2.0),
+            (2_000, 2_000, 4.0),
+        ] {
+            assert!(
+                options(query(width, height, scale)).is_err(),
+                "{}x{}@{}",
+                width,
+                height,
+                scale
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        let server = RenderServer {
+            max_body: 16,
+            concurrency: 1,
+            ..RenderServer::default()
+        };
+
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = server
+            .clone()
+            .router()
+            .oneshot(request("GET", "/health", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .clone()
+            .router()
+            .oneshot(request("POST", "/render?format=gif", "<p>x</p>"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = server
+            .clone()
+            .router()
+            .oneshot(request("POST", "/render?width=100000&height=100000", "x"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // A refresh to a local file is refused before Chrome runs.
+        let response = RenderServer::default()
+            .router()
+            .oneshot(request(
+                "POST",
+                "/render?mime=html",
+                r#"<meta http-equiv="refresh" content="0;url=file:///etc/passwd">"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("http-equiv"));
+
+        let response = server
+            .router()
+            .oneshot(request("POST", "/render", &"x".repeat(17)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}